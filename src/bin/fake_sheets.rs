@@ -0,0 +1,360 @@
+// Minimal stand-in for the Google Sheets values API (get, batchGet, update and
+// batchUpdate), serving sheets from a JSON fixture.
+//
+// Usage: cargo run --bin fake_sheets -- src/config/fake_sheets.json [127.0.0.1:8085]
+// tests/fake_sheets.rs runs it the same way against a full guest-checkin run.
+//
+// Point a run at it with a config containing:
+//   [sheets]
+//   base_url = "http://127.0.0.1:8085/"
+//   auth = "none"
+//
+// The fixture maps spreadsheet ids to sheet names to rows of cells:
+//   { "spreadsheets": { "<spreadsheet id>": { "<sheet name>": [["Header", ...], ["Value", ...]] } } }
+//
+// Updates are kept in memory for the lifetime of the server and logged to stdout.
+// A batch update is applied as a whole or not at all, like the real API.
+//
+// To exercise retries, FAKE_SHEETS_FAIL_FIRST=<n> answers the first n requests with
+// 503 Service Unavailable, with a Retry-After of FAKE_SHEETS_RETRY_AFTER seconds if set.
+// With FAKE_SHEETS_FAIL_ONLY=<suffix>, e.g. values:batchUpdate, only requests whose
+// path ends with it are failed.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use hyper::service::{make_service_fn, service_fn};
+use log::{info, warn, LevelFilter};
+use serde_derive::Deserialize;
+use serde_json::{json, Value};
+
+
+type Sheet = Vec<Vec<Value>>;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Fixture {
+    spreadsheets: HashMap<String, HashMap<String, Sheet>>,
+}
+
+struct Server {
+    fixture: Mutex<Fixture>,
+    failures_left: AtomicU32,
+    fail_only: Option<String>,
+    retry_after: Option<String>,
+}
+
+type State = Arc<Server>;
+
+// Status and message of a failed request, rendered like a Google API error
+type ApiError = (StatusCode, String);
+
+// A parsed A1 range such as "Sheet1!M2:M", "Sheet1!5:5" or "'My Sheet'!A1:C3".
+// Bounds are zero based and inclusive, None means unbounded.
+struct A1Range {
+    sheet: String,
+    start_row: usize,
+    end_row: Option<usize>,
+    start_col: usize,
+    end_col: Option<usize>,
+}
+
+impl A1Range {
+    fn parse(range: &str) -> Option<Self> {
+        let (sheet, cells) = match range.rsplit_once('!') {
+            Some((sheet, cells)) => (sheet, Some(cells)),
+            None => (range, None),
+        };
+        let sheet = sheet.trim_matches('\'').replace("''", "'");
+
+        let mut parsed = A1Range { sheet, start_row: 0, end_row: None, start_col: 0, end_col: None };
+        let Some(cells) = cells else { return Some(parsed) };
+
+        let (start, end) = cells.split_once(':').unwrap_or((cells, cells));
+        let (start_col, start_row) = Self::parse_cell(start)?;
+        let (end_col, end_row) = Self::parse_cell(end)?;
+        parsed.start_col = start_col.unwrap_or(0);
+        parsed.start_row = start_row.unwrap_or(0);
+        parsed.end_col = end_col;
+        parsed.end_row = end_row;
+        Some(parsed)
+    }
+
+    // Splits a cell reference like "AB12" into zero based column and row
+    fn parse_cell(cell: &str) -> Option<(Option<usize>, Option<usize>)> {
+        let letters: String = cell.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+        let digits = &cell[letters.len()..];
+
+        let col = if letters.is_empty() {
+            None
+        } else {
+            Some(letters.to_uppercase().bytes().fold(0, |acc, b| acc * 26 + (b - b'A' + 1) as usize) - 1)
+        };
+        let row = if digits.is_empty() {
+            None
+        } else {
+            Some(digits.parse::<usize>().ok()?.checked_sub(1)?)
+        };
+        Some((col, row))
+    }
+
+    // Cells inside the range, trimming trailing empty rows and cells like the real API
+    fn read(&self, sheet: &Sheet) -> Sheet {
+        let end_row = self.end_row.map(|r| r + 1).unwrap_or(sheet.len()).min(sheet.len());
+        let mut rows: Sheet = (self.start_row..end_row.max(self.start_row))
+            .map(|r| {
+                let row = &sheet[r];
+                let end_col = self.end_col.map(|c| c + 1).unwrap_or(row.len()).min(row.len());
+                let mut cells: Vec<Value> = row.get(self.start_col..end_col.max(self.start_col))
+                    .map(|cells| cells.to_vec())
+                    .unwrap_or_default();
+                while cells.last().is_some_and(is_empty) {
+                    cells.pop();
+                }
+                cells
+            })
+            .collect();
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+        rows
+    }
+
+    // Writes values starting at the top left cell of the range, growing the sheet as needed
+    fn write(&self, sheet: &mut Sheet, values: &Sheet) -> usize {
+        let mut updated = 0;
+        for (r, row) in values.iter().enumerate() {
+            let r = self.start_row + r;
+            if sheet.len() <= r {
+                sheet.resize(r + 1, Vec::new());
+            }
+            for (c, value) in row.iter().enumerate() {
+                let c = self.start_col + c;
+                if sheet[r].len() <= c {
+                    sheet[r].resize(c + 1, json!(""));
+                }
+                sheet[r][c] = value.clone();
+                updated += 1;
+            }
+        }
+        updated
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    value.is_null() || value.as_str() == Some("")
+}
+
+// Decodes %XX escapes, + is left alone as in URL paths
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match u8::from_str_radix(&input[i + 1..i + 3], 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    },
+                    Err(_) => out.push(b'%'),
+                }
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn query_params(query: Option<&str>) -> Vec<(String, String)> {
+    query.unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            // Query strings also encode spaces as +
+            (percent_decode(&k.replace('+', " ")), percent_decode(&v.replace('+', " ")))
+        })
+        .collect()
+}
+
+fn json_response(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn error_response((status, message): ApiError) -> Response<Body> {
+    json_response(status, json!({
+        "error": { "code": status.as_u16(), "message": message, "status": status.canonical_reason() }
+    }))
+}
+
+fn value_range(fixture: &Fixture, spreadsheet_id: &str, range: &str) -> Result<Value, ApiError> {
+    let a1 = A1Range::parse(range)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unable to parse range: {}", range)))?;
+    let sheet = fixture.spreadsheets.get(spreadsheet_id)
+        .and_then(|sheets| sheets.get(&a1.sheet))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unable to parse range: {}", range)))?;
+
+    let values = a1.read(sheet);
+    let mut body = json!({ "range": range, "majorDimension": "ROWS" });
+    if !values.is_empty() {
+        body["values"] = json!(values);
+    }
+    Ok(body)
+}
+
+fn update_range(fixture: &mut Fixture, spreadsheet_id: &str, range: &str, values: &Sheet) -> Result<Value, ApiError> {
+    let a1 = A1Range::parse(range)
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unable to parse range: {}", range)))?;
+    let sheet = fixture.spreadsheets.get_mut(spreadsheet_id)
+        .and_then(|sheets| sheets.get_mut(&a1.sheet))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Unable to parse range: {}", range)))?;
+
+    let updated_cells = a1.write(sheet, values);
+
+    Ok(json!({
+        "spreadsheetId": spreadsheet_id,
+        "updatedRange": range,
+        "updatedRows": values.len(),
+        "updatedColumns": values.iter().map(Vec::len).max().unwrap_or(0),
+        "updatedCells": updated_cells,
+    }))
+}
+
+async fn handle(req: Request<Body>, state: State) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = percent_decode(req.uri().path());
+    let params = query_params(req.uri().query());
+    let body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
+    info!("{} {}", method, path);
+
+    let Some(rest) = path.strip_prefix("/v4/spreadsheets/") else {
+        return Ok(error_response((StatusCode::NOT_FOUND, String::from("Unknown endpoint"))));
+    };
+
+    // Injected failure, before anything is read or written
+    let may_fail = state.fail_only.as_ref().is_none_or(|suffix| rest.ends_with(suffix.as_str()));
+    if may_fail && state.failures_left.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+        warn!("Failing request with 503");
+        let mut response = error_response((StatusCode::SERVICE_UNAVAILABLE, String::from("The service is currently unavailable.")));
+        if let Some(retry_after) = &state.retry_after {
+            response.headers_mut().insert("Retry-After", retry_after.parse().unwrap());
+        }
+        return Ok(response);
+    }
+
+    let mut fixture = state.fixture.lock().unwrap();
+    let response = if let Some(spreadsheet_id) = rest.strip_suffix("/values:batchGet") {
+        let ranges: Result<Vec<Value>, _> = params.iter()
+            .filter(|(k, _)| k == "ranges")
+            .map(|(_, range)| value_range(&fixture, spreadsheet_id, range))
+            .collect();
+        match ranges {
+            Ok(ranges) => json_response(StatusCode::OK, json!({ "spreadsheetId": spreadsheet_id, "valueRanges": ranges })),
+            Err(e) => error_response(e),
+        }
+    } else if let Some(spreadsheet_id) = rest.strip_suffix("/values:batchUpdate") {
+        let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+        let data = request["data"].as_array().cloned().unwrap_or_default();
+
+        // Written to a copy that replaces the fixture only if every range succeeds,
+        // so a bad request changes nothing
+        let mut updated = fixture.clone();
+        let responses: Result<Vec<Value>, _> = data.iter()
+            .map(|d| {
+                let values: Sheet = serde_json::from_value(d["values"].clone()).unwrap_or_default();
+                update_range(&mut updated, spreadsheet_id, d["range"].as_str().unwrap_or_default(), &values)
+            })
+            .collect();
+        match responses {
+            Ok(responses) => {
+                *fixture = updated;
+                for d in &data {
+                    info!("Updated {} {} -> {}", spreadsheet_id, d["range"].as_str().unwrap_or_default(), d["values"]);
+                }
+                json_response(StatusCode::OK, json!({
+                    "spreadsheetId": spreadsheet_id,
+                    "totalUpdatedCells": responses.iter().filter_map(|r| r["updatedCells"].as_u64()).sum::<u64>(),
+                    "responses": responses,
+                }))
+            },
+            Err(e) => {
+                warn!("Batch update rejected, nothing written: {}", e.1);
+                error_response(e)
+            },
+        }
+    } else if let Some((spreadsheet_id, range)) = rest.split_once("/values/") {
+        match method {
+            Method::GET => match value_range(&fixture, spreadsheet_id, range) {
+                Ok(body) => json_response(StatusCode::OK, body),
+                Err(e) => error_response(e),
+            },
+            Method::PUT => {
+                let request: Value = serde_json::from_slice(&body).unwrap_or_default();
+                let values: Sheet = serde_json::from_value(request["values"].clone()).unwrap_or_default();
+                match update_range(&mut fixture, spreadsheet_id, range, &values) {
+                    Ok(body) => {
+                        info!("Updated {} {} -> {}", spreadsheet_id, range, json!(values));
+                        json_response(StatusCode::OK, body)
+                    },
+                    Err(e) => error_response(e),
+                }
+            },
+            _ => error_response((StatusCode::METHOD_NOT_ALLOWED, String::from("Method not allowed"))),
+        }
+    } else {
+        error_response((StatusCode::NOT_FOUND, String::from("Unknown endpoint")))
+    };
+
+    Ok(response)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "[{} {}] {}",
+                humantime::format_rfc3339_seconds(SystemTime::now()),
+                record.level(),
+                message
+            ))
+        })
+        .level(LevelFilter::Info)
+        .chain(std::io::stdout())
+        .apply()?;
+
+    let args: Vec<String> = env::args().collect();
+    let fixture_path = args.get(1).ok_or("Usage: fake_sheets <fixture.json> [address]")?;
+    let addr: SocketAddr = args.get(2).map(String::as_str).unwrap_or("127.0.0.1:8085").parse()?;
+
+    let fixture: Fixture = serde_json::from_str(&fs::read_to_string(fixture_path)?)?;
+    let state: State = Arc::new(Server {
+        fixture: Mutex::new(fixture),
+        failures_left: AtomicU32::new(env::var("FAKE_SHEETS_FAIL_FIRST").ok().and_then(|n| n.parse().ok()).unwrap_or(0)),
+        fail_only: env::var("FAKE_SHEETS_FAIL_ONLY").ok().filter(|suffix| !suffix.is_empty()),
+        retry_after: env::var("FAKE_SHEETS_RETRY_AFTER").ok(),
+    });
+
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(req, state.clone()))) }
+    });
+
+    info!("Fake Sheets server serving {} on http://{}/", fixture_path, addr);
+    hyper::Server::bind(&addr).serve(make_svc).await?;
+
+    Ok(())
+}
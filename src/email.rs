@@ -5,8 +5,56 @@ use rusoto_sesv2::{Destination, EmailContent, RawMessage, SendEmailRequest, SesV
 use std::{fs::File, str::FromStr};
 use std::io::Read;
 use base64::encode;
+use crate::error::CheckinError;
 
 
+// A titled HTML table in the report email
+pub struct EmailTable {
+    title: String,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl EmailTable {
+    pub fn new(title: &str, headers: &[&str], rows: Vec<Vec<String>>) -> Self {
+        Self {
+            title: title.to_string(),
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows,
+        }
+    }
+
+    fn to_html(&self) -> String {
+        let mut table_rows = String::new();
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            table_rows.push_str("<tr>");
+            for cell in row {
+                table_rows.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            table_rows.push_str("</tr>");
+        }
+
+        format!(
+            r#"
+                <h2 style="color: #1E90FF;">{}</h2>
+                <table border="1">
+                    {}
+                </table>
+                <br>"#,
+            escape_html(&self.title), table_rows
+        )
+    }
+}
+
+// Guest answers and calendar text are shown as typed, never as markup
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
 pub struct Email {
     attachments: Vec<String>,
     from: String,
@@ -25,29 +73,29 @@ impl Email {
         access_key: &str,
         secret_key: &str,
         region: &str,
-    ) -> Self {
+    ) -> Result<Self, CheckinError> {
         
-        let region_object = match Region::from_str(region) {
-            Ok(region) => region,
-            Err(_) => panic!("Improper AWS region name given"),
-        };
+        let region_object = Region::from_str(region)
+            .map_err(|_| CheckinError::Config(format!("Improper AWS region name given: \"{}\"", region)))?;
 
         let credentials_provider = StaticProvider::new_minimal(
             access_key.to_string(),
             secret_key.to_string()
         );
 
-        Self {
-            attachments: attachments,
-            from: from,
-            to: to,
+        Ok(Self {
+            attachments,
+            from,
+            to,
             subject: subject.to_string(),
             credentials: credentials_provider,
             region: region_object,
-        }
+        })
     }
 
-    pub async fn send(&self, unregistered_guests: Vec<Vec<String>>, checkin_issues: Vec<Vec<String>>) {
+    // Sends the report. A missing header image or attachment is left out and
+    // listed in the email rather than stopping it from being sent.
+    pub async fn send(&self, tables: &[EmailTable]) -> Result<(), CheckinError> {
 
         // Create the raw email message with multiple attachments
         let mut recipients = String::new();
@@ -55,71 +103,60 @@ impl Email {
             recipients.push_str(&format!("{}, ", &recipient));
         }
 
-        // Unregistered Guests Table
-        let mut table_data: Vec<Vec<String>> = Vec::new();
-        table_data.push(vec!["Listing".to_string(), "Row".to_string(), "Fullname".to_string(), "Check In".to_string(), "Check Out".to_string()]);
-        for guest in unregistered_guests {
-            table_data.push(guest);
-        }
+        let inline_image_path = "src/header_image.jpg";
 
-        // Generate HTML unregistered guests table rows
-        let mut unreg_guests_table_rows = String::new();
-        for row in table_data {
-            unreg_guests_table_rows.push_str("<tr>");
-            for cell in row {
-                unreg_guests_table_rows.push_str(&format!("<td>{}</td>", cell));
-            }
-            unreg_guests_table_rows.push_str("</tr>");
-        }
+        // Load the inline image file
+        let encoded_image = match Self::read_encoded(inline_image_path) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                warn!("Leaving out header image {}: {}", inline_image_path, e);
+                None
+            },
+        };
 
-        // Guests with checkin issues
-        let mut table_data: Vec<Vec<String>> = Vec::new();
-        table_data.push(vec!["Listing".to_string(), "Row".to_string(), "Fullname".to_string(), "Input Error(s)".to_string()]);
-        for guest in checkin_issues {
-            table_data.push(guest);
+        // Load the attachment files
+        let mut attachments: Vec<(&str, String)> = Vec::new();
+        let mut missing_attachments: Vec<Vec<String>> = Vec::new();
+        for attachment in &self.attachments {
+            info!("Attaching file {} to email", attachment);
+            match Self::read_encoded(attachment) {
+                Ok(encoded_file) => attachments.push((attachment, encoded_file)),
+                Err(e) => {
+                    warn!("Leaving out attachment {}: {}", attachment, e);
+                    missing_attachments.push(vec![attachment.clone(), e.to_string()]);
+                },
+            }
         }
 
-        // Generate HTML checkin issues table rows
-        let mut checkin_issues_table_rows = String::new();
-        for row in table_data {
-            checkin_issues_table_rows.push_str("<tr>");
-            for cell in row {
-                checkin_issues_table_rows.push_str(&format!("<td>{}</td>", cell));
-            }
-            checkin_issues_table_rows.push_str("</tr>");
+        // Generate HTML tables
+        let mut tables_html: String = tables.iter().map(EmailTable::to_html).collect();
+        if !missing_attachments.is_empty() {
+            tables_html.push_str(&EmailTable::new(
+                "Attachments Not Included",
+                &["File", "Error"],
+                missing_attachments,
+            ).to_html());
         }
 
-        let inline_image_path = "src/header_image.jpg";
+        let image_html = match encoded_image {
+            Some(_) => r#"<img src="cid:header_image.jpg" alt="Image" style="width:100%; max-width:600px;">"#,
+            None => "",
+        };
 
         // HTML content with tables and an image
         let html_content = format!(
             r#"
             <html>
             <body>
-                <img src="cid:header_image.jpg" alt="Image" style="width:100%; max-width:600px;">
-                <br>
-                <br>
-                <h2 style="color: #1E90FF;">Guests Available for Checkin</h2>
-                <table border="1">
-                    {}
-                </table>
+                {}
                 <br>
-                <h2 style="color: #1E90FF;">Guests with Checkin Issues</h2>
-                <table border="1">
-                    {}
-                </table>
+                <br>{}
             </body>
             </html>
             "#,
-            unreg_guests_table_rows, checkin_issues_table_rows
+            image_html, tables_html
         );
 
-        // Load the inline image file
-        let mut image_file = File::open(inline_image_path).expect("Unable to open image file");
-        let mut image_content = Vec::new();
-        image_file.read_to_end(&mut image_content).expect("Unable to read image file");
-        let encoded_image = encode(&image_content);
-
         let mut raw_email = String::new();
         raw_email.push_str(
             &format!(
@@ -138,28 +175,27 @@ impl Email {
                 Content-Type: text/html; charset=\"UTF-8\"\r\n\
                 Content-Transfer-Encoding: 7bit\r\n\r\n\
                 {}\r\n\r\n\
-                --subboundary--\r\n\
-                --boundary\r\n\
+                --subboundary--\r\n",
+                self.from, recipients, self.subject, html_content
+            )
+        );
+
+        if let Some(encoded_image) = encoded_image {
+            raw_email.push_str(&format!(
+                "--boundary\r\n\
                 Content-Type: image/jpeg; name=\"header_image.jpg\"\r\n\
                 Content-Transfer-Encoding: base64\r\n\
                 Content-Disposition: inline; filename=\"header_image.jpg\"\r\n\
                 Content-ID: <header_image.jpg>\r\n\r\n\
                 {}\r\n",
-                self.from, recipients, self.subject, html_content, encoded_image
-            )
-        );
+                encoded_image
+            ));
+        }
 
-        for attachment in &self.attachments {
-            
-            // Load the attachment file
-            info!("Attaching file {} to email", attachment);
-            let mut file = File::open(attachment).expect("Unable to open file");
-            let mut file_content = Vec::new();
-            file.read_to_end(&mut file_content).expect("Unable to read file");
-            let encoded_file = encode(&file_content);
+        for (attachment, encoded_file) in attachments {
 
             // Get the file name from the path
-            let file_name = attachment.split('/').last().unwrap_or("attachment");
+            let file_name = attachment.split('/').next_back().unwrap_or("attachment");
 
             // Append attachment part
             raw_email.push_str(&format!(
@@ -177,7 +213,7 @@ impl Email {
 
         // Create the SES client
         let client = SesV2Client::new_with(
-            HttpClient::new().expect("Failed to create HTTP client"),
+            HttpClient::new().map_err(|e| CheckinError::Notification(format!("Failed to create HTTP client: {}", e)))?,
             self.credentials.clone(),
             self.region.clone(),
         );
@@ -199,9 +235,18 @@ impl Email {
         };
 
         // Send the email
-        match client.send_email(request).await {
-            Ok(_) => info!("Email sent successfully!"),
-            Err(e) => warn!("Error sending email: {:?}", e),
-        }
+        client.send_email(request).await
+            .map_err(|e| CheckinError::Notification(format!("Error sending email: {:?}", e)))?;
+        info!("Email sent successfully!");
+
+        Ok(())
+    }
+
+    // Reads a file as base64 for a MIME part
+    fn read_encoded(path: &str) -> std::io::Result<String> {
+        let mut file = File::open(path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        Ok(encode(&content))
     }
 }
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fmt;
+
+
+// Errors of a Guest Checkin run. A listing failing with one of these is
+// reported in the run email, the other listings carry on.
+#[derive(Debug)]
+pub enum CheckinError {
+    Config(String),
+    Auth(String),
+    SheetAccess(String),
+    Parse(String),
+    UnlWrite(String, std::io::Error),
+    // A local file other than the UNL file, such as a sidecar state file
+    Io(String, std::io::Error),
+    Calendar(String),
+    Notification(String),
+}
+
+impl From<google_sheets4::Error> for CheckinError {
+    fn from(error: google_sheets4::Error) -> Self {
+        match error {
+            google_sheets4::Error::MissingToken(e) => CheckinError::Auth(format!("Google token retrieval failed: {}", e)),
+            e => CheckinError::SheetAccess(format!("Google Sheets API: {}", e.to_string().trim_end())),
+        }
+    }
+}
+
+impl From<calamine::Error> for CheckinError {
+    fn from(error: calamine::Error) -> Self {
+        CheckinError::SheetAccess(format!("Workbook: {}", error))
+    }
+}
+
+impl From<csv::Error> for CheckinError {
+    fn from(error: csv::Error) -> Self {
+        match error.kind() {
+            csv::ErrorKind::Io(_) => CheckinError::SheetAccess(format!("CSV: {}", error)),
+            _ => CheckinError::Parse(format!("CSV: {}", error)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for CheckinError {
+    fn from(error: serde_json::Error) -> Self {
+        CheckinError::Parse(format!("State file: {}", error))
+    }
+}
+
+impl fmt::Display for CheckinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckinError::Config(e) => write!(f, "Configuration Error: {}", e),
+            CheckinError::Auth(e) => write!(f, "Authentication Error: {}", e),
+            CheckinError::SheetAccess(e) => write!(f, "Sheet Access Error: {}", e),
+            CheckinError::Parse(e) => write!(f, "Parse Error: {}", e),
+            CheckinError::UnlWrite(file, e) => write!(f, "UNL File Error for {}: {}", file, e),
+            CheckinError::Io(file, e) => write!(f, "File Error for {}: {}", file, e),
+            CheckinError::Calendar(e) => write!(f, "Calendar Error: {}", e),
+            CheckinError::Notification(e) => write!(f, "Notification Error: {}", e),
+        }
+    }
+}
+
+impl Error for CheckinError {}
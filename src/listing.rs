@@ -1,55 +1,160 @@
+mod arrival;
+mod calendar;
+mod columns;
+mod country;
+mod csv_reservation;
+mod dates;
+mod duplicates;
 mod guest;
+mod mrz;
+mod purpose;
 mod reservation;
+mod sidecar;
+mod source;
+mod status;
+mod stay;
+mod translit;
+mod visa;
+mod workbook_reservation;
 
+use crate::error::CheckinError;
+use crate::listing::arrival::ArrivalRules;
+pub use crate::listing::arrival::ArrivalDecision;
+pub use crate::listing::calendar::CalendarReport;
+use crate::listing::csv_reservation::CsvReservation;
 use crate::listing::guest::Guest;
 use crate::listing::reservation::Reservation;
+pub use crate::listing::reservation::{build_hub, SheetsHub};
+use crate::listing::source::ReservationSource;
+pub use crate::listing::status::{RegistrationStatus, StatusUpdate};
+use crate::listing::stay::StayRules;
+pub use crate::listing::visa::VisaRules;
+use crate::listing::workbook_reservation::WorkbookReservation;
+use crate::retry::RetryPolicy;
+use crate::settings::{self, SheetsApi, SourceKind};
 use chrono::{prelude::*, FixedOffset};
+use std::sync::Arc;
 
 
-#[derive(Clone)]
 pub struct Listing {
-    id: String,
     name: String,
-    address: String,
-    service_account_key_filepath: String,
-    spreadsheet_id: String,
-    sheet_name: String,
-    reservation: Reservation,
+    reservation: Box<dyn ReservationSource>,
     a_record: String,
+    ical: Option<String>,
+    ical_lookback_days: i64,
+    arrival_rules: ArrivalRules,
+    stay_rules: StayRules,
+    visa_rules: Arc<VisaRules>,
 }
 
 impl Listing {
-    pub async fn new(id: &str, name: &str, address: &str, spreadsheet_id: &str, sheet_name: &str, 
-                     a_record: &str, service_account_key_filepath: &str) -> Self {
-        Listing {
-            id: id.to_string(),
-            name: name.to_string(),
-            address: address.to_string(),
-            spreadsheet_id: spreadsheet_id.to_string(),
-            sheet_name: sheet_name.to_string(),
-            a_record: Self::add_datetime(a_record),
-            service_account_key_filepath: service_account_key_filepath.to_string(),
-            reservation: Reservation::new(spreadsheet_id, sheet_name, service_account_key_filepath).await,
-        }
+    // Google Sheets listings use shared_hub unless they configure their own service account
+    pub async fn new(
+        config: &settings::Listing,
+        sheets_api: &SheetsApi,
+        shared_hub: Option<&SheetsHub>,
+        retry: &Arc<RetryPolicy>,
+        visa_rules: &Arc<VisaRules>,
+    ) -> Result<Self, CheckinError> {
+        let reservation: Box<dyn ReservationSource> = match config.source {
+            SourceKind::GoogleSheets => {
+                if config.google_spreadsheet_id.is_empty() {
+                    return Err(CheckinError::Config(String::from("google_spreadsheet_id is not set")));
+                }
+                let hub = match config.own_service_account_key() {
+                    Some(key_filepath) => build_hub(sheets_api, key_filepath).await?,
+                    None => shared_hub
+                        .ok_or_else(|| CheckinError::Auth(String::from("The shared Google Sheets client could not be created")))?
+                        .clone(),
+                };
+                Box::new(Reservation::new(
+                    &config.google_spreadsheet_id,
+                    &config.google_sheet_name,
+                    &config.columns,
+                    config.retry_exported,
+                    hub,
+                    retry.clone(),
+                ))
+            },
+            SourceKind::Csv | SourceKind::Workbook if config.source_filepath.is_empty() => {
+                return Err(CheckinError::Config(String::from("source_filepath is not set")));
+            },
+            SourceKind::Csv => Box::new(CsvReservation::new(
+                &config.source_filepath,
+                &config.columns,
+                config.retry_exported,
+            )),
+            SourceKind::Workbook => Box::new(WorkbookReservation::new(
+                &config.source_filepath,
+                [config.workbook_sheet_name.as_deref().unwrap_or_default(), config.google_sheet_name.as_str()]
+                    .into_iter()
+                    .find(|name| !name.is_empty()),
+                &config.columns,
+                config.retry_exported,
+            )),
+        };
+
+        Ok(Listing {
+            name: config.name.to_string(),
+            a_record: Self::add_datetime(&config.a_record),
+            reservation,
+            ical: config.ical.clone().filter(|ical| !ical.is_empty()),
+            ical_lookback_days: config.ical_lookback_days,
+            arrival_rules: ArrivalRules::new(config),
+            stay_rules: StayRules::new(config),
+            visa_rules: visa_rules.clone(),
+        })
     }
 
     pub fn get_name(&self) -> &str {&self.name}
     pub fn get_a_record(&self) -> &str {&self.a_record}
 
-    pub async fn find_unregistered_guests(&self) -> Vec<Guest> {
-        let unregistered_guests =  self.reservation.find_unregistered_guests().await;
-        unregistered_guests
+    // Pending guests, with the listing's stay and visa rules added to their input checks
+    pub async fn find_unregistered_guests(&mut self) -> Result<Vec<Guest>, CheckinError> {
+        let mut guests = self.reservation.find_pending_guests().await?;
+        let today = Self::prague_now().date_naive();
+
+        for guest in &mut guests {
+            for e in self.stay_rules.check(guest, today) {
+                guest.add_issue(e);
+            }
+            if let Some(e) = self.visa_rules.check(guest) {
+                guest.add_issue(e);
+            }
+        }
+
+        Ok(guests)
     }
     
-    pub async fn update_guest_as_registered(&self, row: &str, first_name: &str, last_name: &str) {
-        self.reservation.update_registered_with_authorities(row, first_name, last_name).await;
+    // Compares the listing's booking calendar against guest form rows,
+    // None when the listing has no calendar configured
+    pub async fn check_calendar(&mut self) -> Result<Option<CalendarReport>, CheckinError> {
+        let Some(ical) = &self.ical else { return Ok(None) };
+
+        let bookings = calendar::load_bookings(ical).await?;
+        let stays = self.reservation.find_stays().await?;
+
+        Ok(Some(calendar::cross_check(&bookings, &stays, self.ical_lookback_days, Self::prague_now().date_naive())))
     }
 
-    fn add_datetime(a_record: &str) -> String {
+    // Commits all status changes of a run for this listing in one batch
+    pub async fn update_guest_statuses(&self, updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError> {
+        self.reservation.mark_guest_states(updates, run_id).await
+    }
+
+    // Applies the listing's arrival rules to a guest, relative to today in Prague
+    pub fn check_arrival(&self, guest: &Guest) -> ArrivalDecision {
+        self.arrival_rules.check(&guest.check_in, Self::prague_now().date_naive())
+    }
+
+    fn prague_now() -> DateTime<FixedOffset> {
         let utc_now: DateTime<Utc> = Utc::now();
-        let prague_offset = FixedOffset::east_opt(1 * 3600).expect("Invalid offset");
-        let prague_now: DateTime<FixedOffset> = utc_now.with_timezone(&prague_offset);
-        let formatted_datetime = prague_now.format("%Y.%m.%d %H:%M:%S").to_string();
+        let prague_offset = FixedOffset::east_opt(3600).expect("Invalid offset");
+        utc_now.with_timezone(&prague_offset)
+    }
+
+    fn add_datetime(a_record: &str) -> String {
+        let formatted_datetime = Self::prague_now().format("%Y.%m.%d %H:%M:%S").to_string();
         a_record.replace("AddDate", &formatted_datetime)
     }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use chrono::NaiveDate;
+use crate::listing::dates::FormDate;
+use crate::settings;
+
+
+// What a listing's arrival rules decide for a guest ready to be exported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrivalDecision {
+    Export,
+    // Exported, but after the legal reporting deadline
+    ExportLate(String),
+    // Left out of this run's UNL file
+    Exclude(String),
+}
+
+// Per listing rules on which arrivals are exported in a run
+#[derive(Debug, Clone)]
+pub struct ArrivalRules {
+    window_days: Option<i64>,
+    hold_future_arrivals: bool,
+    reporting_deadline_days: Option<i64>,
+}
+
+impl ArrivalRules {
+    pub fn new(config: &settings::Listing) -> Self {
+        ArrivalRules {
+            window_days: config.arrival_window_days,
+            hold_future_arrivals: config.hold_future_arrivals,
+            reporting_deadline_days: config.reporting_deadline_days,
+        }
+    }
+
+    // Decides on a guest by check-in date. Dates that could not be read are
+    // left to input validation and exported as before. The window only holds back
+    // future arrivals, a guest who already arrived is always exported, late or not.
+    pub fn check(&self, check_in: &FormDate, today: NaiveDate) -> ArrivalDecision {
+        let Some(arrival) = check_in.get() else {
+            return ArrivalDecision::Export;
+        };
+        let days_since_arrival = (today - arrival).num_days();
+
+        if days_since_arrival < 0 {
+            if self.hold_future_arrivals {
+                return ArrivalDecision::Exclude(format!("Arrives {}, held until the day of arrival", check_in));
+            }
+            if let Some(window) = self.window_days.filter(|window| -days_since_arrival > *window) {
+                return ArrivalDecision::Exclude(format!("Arrives {}, more than {} days ahead", check_in, window));
+            }
+            return ArrivalDecision::Export;
+        }
+
+        if let Some(deadline) = self.reporting_deadline_days.filter(|deadline| days_since_arrival > *deadline) {
+            return ArrivalDecision::ExportLate(format!("Arrived {}, past the {} day reporting deadline", check_in, deadline));
+        }
+
+        ArrivalDecision::Export
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs;
+use chrono::{Duration, NaiveDate};
+use log::{debug, info};
+use crate::error::CheckinError;
+use crate::listing::dates::FormDate;
+
+
+// A booking taken from a listing's Airbnb/Booking.com iCal export
+#[derive(Clone, Debug)]
+pub struct Booking {
+    pub summary: String,
+    pub check_in: NaiveDate,
+    pub check_out: NaiveDate,
+}
+
+// Stay dates of one guest form row, whatever its registration status
+#[derive(Clone, Debug)]
+pub struct GuestStay {
+    pub row: String,
+    pub guest_name: String,
+    pub check_in: FormDate,
+    pub check_out: FormDate,
+}
+
+// Bookings and guest rows that do not line up with each other
+#[derive(Debug, Default)]
+pub struct CalendarReport {
+    pub bookings_without_guests: Vec<Booking>,
+    pub stays_without_booking: Vec<GuestStay>,
+    // Overlapping bookings and guest rows whose dates differ, e.g. after a changed booking
+    pub date_differences: Vec<(Booking, GuestStay)>,
+}
+
+// Loads bookings from a local .ics file or an http(s) URL
+pub async fn load_bookings(location: &str) -> Result<Vec<Booking>, CheckinError> {
+    let content = if location.starts_with("http://") || location.starts_with("https://") {
+        reqwest::get(location).await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| CheckinError::Calendar(format!("{}: {}", location, e)))?
+            .text().await
+            .map_err(|e| CheckinError::Calendar(format!("{}: {}", location, e)))?
+    } else {
+        fs::read_to_string(location).map_err(|e| CheckinError::Io(location.to_string(), e))?
+    };
+
+    let bookings = parse_bookings(&content);
+    info!("{} bookings found in calendar {}", bookings.len(), location);
+
+    Ok(bookings)
+}
+
+// Parses VEVENTs of an iCal document, skipping blocked dates
+fn parse_bookings(content: &str) -> Vec<Booking> {
+    // Unfold continuation lines, which start with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+
+    let mut bookings = Vec::new();
+    let (mut summary, mut start, mut end) = (String::new(), None, None);
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        // Drop parameters such as DTSTART;VALUE=DATE
+        let name = name.split(';').next().unwrap_or_default().to_uppercase();
+
+        match name.as_str() {
+            "BEGIN" if value == "VEVENT" => (summary, start, end) = (String::new(), None, None),
+            "SUMMARY" => summary = unescape_text(value),
+            "DTSTART" => start = parse_ical_date(value),
+            "DTEND" => end = parse_ical_date(value),
+            "END" if value == "VEVENT" => {
+                let blocked = summary.to_lowercase().contains("not available")
+                    || summary.to_lowercase().contains("blocked");
+                match (start, end) {
+                    (Some(check_in), Some(check_out)) if !blocked => bookings.push(Booking {
+                        summary: summary.clone(),
+                        check_in,
+                        check_out,
+                    }),
+                    _ => debug!("Skipping calendar event {}", summary),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    bookings
+}
+
+// Undoes the escaping of iCal TEXT values: \\, \; \, and \n
+fn unescape_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+// Reads the date part of DATE (20240601) and DATE-TIME (20240601T140000Z) values
+fn parse_ical_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(0..8)?, "%Y%m%d").ok()
+}
+
+// Compares bookings that started within lookback_days before today against guest rows.
+// A booking and a row belong together when their dates overlap. Those that do not
+// share the exact dates, and have no exact match elsewhere, are listed as differences.
+pub fn cross_check(bookings: &[Booking], stays: &[GuestStay], lookback_days: i64, today: NaiveDate) -> CalendarReport {
+    let window_start = today - Duration::days(lookback_days);
+    let in_window = |date: NaiveDate| date >= window_start && date <= today;
+
+    let stay_dates = |stay: &GuestStay| -> Option<(NaiveDate, NaiveDate)> {
+        Some((
+            stay.check_in.get()?,
+            stay.check_out.get()?,
+        ))
+    };
+    let exact = |booking: &Booking, stay: &GuestStay| stay_dates(stay) == Some((booking.check_in, booking.check_out));
+    let overlaps = |booking: &Booking, stay: &GuestStay| matches!(
+        stay_dates(stay),
+        Some((check_in, check_out)) if check_in < booking.check_out && booking.check_in < check_out
+    );
+    let stay_in_window = |stay: &GuestStay| matches!(stay_dates(stay), Some((check_in, _)) if in_window(check_in));
+
+    let bookings_without_guests = bookings.iter()
+        .filter(|booking| in_window(booking.check_in))
+        .filter(|booking| !stays.iter().any(|stay| overlaps(booking, stay)))
+        .cloned()
+        .collect();
+
+    let stays_without_booking = stays.iter()
+        .filter(|stay| stay_in_window(stay))
+        .filter(|stay| !bookings.iter().any(|booking| overlaps(booking, stay)))
+        .cloned()
+        .collect();
+
+    let date_differences = bookings.iter()
+        .flat_map(|booking| stays.iter().map(move |stay| (booking, stay)))
+        .filter(|(booking, stay)| in_window(booking.check_in) || stay_in_window(stay))
+        .filter(|(booking, stay)| overlaps(booking, stay) && !exact(booking, stay))
+        .filter(|(booking, stay)| {
+            !stays.iter().any(|other| exact(booking, other)) && !bookings.iter().any(|other| exact(other, stay))
+        })
+        .map(|(booking, stay)| (booking.clone(), stay.clone()))
+        .collect();
+
+    CalendarReport {
+        bookings_without_guests,
+        stays_without_booking,
+        date_differences,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listing::dates::DateParser;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20240601\r
+DTEND;VALUE=DATE:20240605\r
+SUMMARY:Reserved - Schmidt\\, Anna\\; 2 guests\\nHM1234\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20240603\r
+DTEND;VALUE=DATE:20240607\r
+SUMMARY:Airbnb (Not available)\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20240610T140000Z\r
+DTEND:20240612T100000Z\r
+SUMMARY:Reserved - Ro\r
+ ssi\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20240614\r
+DTEND;VALUE=DATE:20240616\r
+SUMMARY:Reserved - Smith\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn stay(row: &str, check_in: &str, check_out: &str) -> GuestStay {
+        let dates = DateParser::new(None);
+        GuestStay {
+            row: row.to_string(),
+            guest_name: format!("Guest {}", row),
+            check_in: dates.parse(check_in),
+            check_out: dates.parse(check_out),
+        }
+    }
+
+    #[test]
+    fn parses_bookings_and_skips_blocked_dates() {
+        let bookings = parse_bookings(ICS);
+        assert_eq!(bookings.len(), 3);
+        assert_eq!(bookings[0].summary, "Reserved - Schmidt, Anna; 2 guests\nHM1234");
+        assert_eq!((bookings[0].check_in, bookings[0].check_out), (date(2024, 6, 1), date(2024, 6, 5)));
+        assert_eq!(bookings[1].summary, "Reserved - Rossi");
+        assert_eq!((bookings[1].check_in, bookings[1].check_out), (date(2024, 6, 10), date(2024, 6, 12)));
+    }
+
+    #[test]
+    fn matches_bookings_and_stays_by_overlap() {
+        let bookings = parse_bookings(ICS);
+        let stays = [
+            stay("2", "01.06.2024", "05.06.2024"),
+            stay("3", "10.06.2024", "13.06.2024"),
+            stay("4", "20.06.2024", "22.06.2024"),
+        ];
+        let report = cross_check(&bookings, &stays, 30, date(2024, 6, 25));
+
+        let summaries = |bookings: &[Booking]| bookings.iter().map(|b| b.summary.clone()).collect::<Vec<_>>();
+        assert_eq!(summaries(&report.bookings_without_guests), ["Reserved - Smith"]);
+        assert_eq!(report.stays_without_booking.iter().map(|s| s.row.as_str()).collect::<Vec<_>>(), ["4"]);
+        // Rossi's row stays a night longer than booked
+        assert_eq!(report.date_differences.len(), 1);
+        assert_eq!(report.date_differences[0].0.summary, "Reserved - Rossi");
+        assert_eq!(report.date_differences[0].1.row, "3");
+    }
+
+    #[test]
+    fn checks_only_the_lookback_window() {
+        let bookings = parse_bookings(ICS);
+        // The 1 June booking without a row is older than the window, Smith's arrives after today
+        let report = cross_check(&bookings, &[stay("3", "10.06.2024", "12.06.2024")], 10, date(2024, 6, 12));
+        assert!(report.bookings_without_guests.is_empty());
+        assert!(report.stays_without_booking.is_empty());
+        assert!(report.date_differences.is_empty());
+
+        let report = cross_check(&bookings, &[], 10, date(2024, 6, 14));
+        assert_eq!(report.bookings_without_guests.len(), 2);
+    }
+}
@@ -0,0 +1,316 @@
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::dates::DateParser;
+use crate::listing::guest::Guest;
+use crate::listing::purpose::PurposeLabels;
+use crate::settings::{ColumnMapping, CompanionMapping};
+
+
+// Zero based positions of each guest field, resolved from the header row
+#[derive(Clone, Debug)]
+pub struct Columns {
+    pub timestamp: usize,
+    pub purpose_of_stay: usize,
+    pub check_in: usize,
+    pub check_out: usize,
+    pub surname: usize,
+    pub first_name: usize,
+    pub birth_date: usize,
+    pub country_of_citizenship: usize,
+    pub travel_doc_number: usize,
+    pub visa_number: usize,
+    pub address_abroad: usize,
+    pub full_name: usize,
+    pub mrz: Option<usize>,
+    pub companions: Vec<CompanionColumns>,
+    pub purpose_labels: PurposeLabels,
+    pub dates: DateParser,
+}
+
+// Zero based positions of one companion's question group
+#[derive(Clone, Debug)]
+pub struct CompanionColumns {
+    pub guest_number: u32,
+    pub surname: usize,
+    pub first_name: usize,
+    pub birth_date: usize,
+    pub country_of_citizenship: usize,
+    pub travel_doc_number: usize,
+    pub visa_number: Option<usize>,
+    pub address_abroad: Option<usize>,
+    pub mrz: Option<usize>,
+}
+
+impl Columns {
+    pub fn from_header(header: &[String], mapping: &ColumnMapping) -> Result<Self, CheckinError> {
+        Ok(Columns {
+            timestamp: Self::find(header, &mapping.timestamp)?,
+            purpose_of_stay: Self::find(header, &mapping.purpose_of_stay)?,
+            check_in: Self::find(header, &mapping.check_in)?,
+            check_out: Self::find(header, &mapping.check_out)?,
+            surname: Self::find(header, &mapping.surname)?,
+            first_name: Self::find(header, &mapping.first_name)?,
+            birth_date: Self::find(header, &mapping.birth_date)?,
+            country_of_citizenship: Self::find(header, &mapping.country_of_citizenship)?,
+            travel_doc_number: Self::find(header, &mapping.travel_doc_number)?,
+            visa_number: Self::find(header, &mapping.visa_number)?,
+            address_abroad: Self::find(header, &mapping.address_abroad)?,
+            full_name: Self::find(header, &mapping.full_name)?,
+            mrz: mapping.mrz.as_deref().map(|name| Self::find(header, name)).transpose()?,
+            companions: match &mapping.companions {
+                Some(companions) => Self::find_companions(header, companions)?,
+                None => Vec::new(),
+            },
+            purpose_labels: PurposeLabels::new(&mapping.purpose_labels)?,
+            dates: DateParser::new(mapping.form_locale.as_deref()),
+        })
+    }
+
+    // Resolves companion question groups 2, 3, ... up to max_guests, stopping at
+    // the first group without a surname column
+    fn find_companions(header: &[String], mapping: &CompanionMapping) -> Result<Vec<CompanionColumns>, CheckinError> {
+        if !mapping.surname.contains("{n}") {
+            return Err(CheckinError::Config(format!("Companion header \"{}\" has no {{n}} placeholder", mapping.surname)));
+        }
+
+        let mut companions = Vec::new();
+        for guest_number in 2..=mapping.max_guests {
+            let name = |template: &str| template.replace("{n}", &guest_number.to_string());
+            let Some(surname) = Self::find_optional(header, &name(&mapping.surname))? else { break };
+
+            companions.push(CompanionColumns {
+                guest_number,
+                surname,
+                first_name: Self::find(header, &name(&mapping.first_name))?,
+                birth_date: Self::find(header, &name(&mapping.birth_date))?,
+                country_of_citizenship: Self::find(header, &name(&mapping.country_of_citizenship))?,
+                travel_doc_number: Self::find(header, &name(&mapping.travel_doc_number))?,
+                visa_number: mapping.visa_number.as_deref().map(|t| Self::find(header, &name(t))).transpose()?,
+                address_abroad: mapping.address_abroad.as_deref().map(|t| Self::find(header, &name(t))).transpose()?,
+                mrz: mapping.mrz.as_deref().map(|t| Self::find(header, &name(t))).transpose()?,
+            });
+        }
+
+        Ok(companions)
+    }
+
+    // Finds the single header cell matching name, ignoring case and surrounding whitespace
+    pub fn find(header: &[String], name: &str) -> Result<usize, CheckinError> {
+        Self::find_optional(header, name)?
+            .ok_or_else(|| CheckinError::Config(format!("No column with header \"{}\" found in sheet", name)))
+    }
+
+    // Like find, but a missing header is None. A header matching several columns is still an error.
+    pub fn find_optional(header: &[String], name: &str) -> Result<Option<usize>, CheckinError> {
+        let wanted = name.trim().to_lowercase();
+        let matches: Vec<usize> = header.iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.trim().to_lowercase() == wanted)
+            .map(|(i, _)| i)
+            .collect();
+
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(Some(matches[0])),
+            _ => Err(CheckinError::Config(format!(
+                "Header \"{}\" matches more than one column ({})",
+                name,
+                matches.iter().map(|i| column_letter(*i)).collect::<Vec<_>>().join(", "),
+            ))),
+        }
+    }
+
+    // Builds a Guest from a sheet row, missing trailing cells are read as empty
+    fn guest_from_row(&self, row_num: &str, row: &[String]) -> Guest {
+        let cell = |i: usize| row.get(i).map(|v| v.trim().to_string()).unwrap_or_default();
+
+        let mut guest = Guest::new(
+            &row_num.to_string(),
+            cell(self.timestamp),
+            self.purpose_labels.parse(&cell(self.purpose_of_stay)),
+            self.dates.parse(&cell(self.check_in)),
+            self.dates.parse(&cell(self.check_out)),
+            cell(self.surname),
+            cell(self.first_name),
+            self.dates.parse_birth_date(&cell(self.birth_date)),
+            cell(self.country_of_citizenship),
+            cell(self.travel_doc_number),
+            cell(self.visa_number),
+            cell(self.address_abroad),
+            cell(self.full_name),
+        );
+        if let Some(mrz) = self.mrz.map(cell).filter(|mrz| !mrz.is_empty()) {
+            guest.check_mrz(&mrz);
+        }
+
+        guest
+    }
+
+    // Builds the lead guest and every filled in companion group of a sheet row
+    pub fn guests_from_row(&self, row_num: &str, row: &[String]) -> Vec<Guest> {
+        let cell = |i: usize| row.get(i).map(|v| v.trim().to_string()).unwrap_or_default();
+        let lead = self.guest_from_row(row_num, row);
+
+        let mut guests = Vec::new();
+        for companion in &self.companions {
+            let group = [companion.surname, companion.first_name, companion.birth_date,
+                companion.country_of_citizenship, companion.travel_doc_number];
+            if group.iter().all(|&i| cell(i).is_empty()) {
+                continue;
+            }
+
+            let surname = cell(companion.surname);
+            let first_name = cell(companion.first_name);
+            let mut guest = Guest::new(
+                &row_num.to_string(),
+                lead.timestamp.clone(),
+                lead.purpose_of_stay.clone(),
+                lead.check_in.clone(),
+                lead.check_out.clone(),
+                surname.clone(),
+                first_name.clone(),
+                self.dates.parse_birth_date(&cell(companion.birth_date)),
+                cell(companion.country_of_citizenship),
+                cell(companion.travel_doc_number),
+                companion.visa_number.map(cell).unwrap_or_default(),
+                companion.address_abroad.map(cell).unwrap_or_else(|| lead.address_abroad.clone()),
+                format!("{} {}", first_name, surname),
+            );
+            guest.guest_number = companion.guest_number;
+            if let Some(mrz) = companion.mrz.map(cell).filter(|mrz| !mrz.is_empty()) {
+                guest.check_mrz(&mrz);
+            }
+            guests.push(guest);
+        }
+
+        guests.insert(0, lead);
+        guests
+    }
+
+    // Reads only the stay dates and guest name of a sheet row
+    pub fn stay_from_row(&self, row_num: &str, row: &[String]) -> GuestStay {
+        let cell = |i: usize| row.get(i).map(|v| v.trim().to_string()).unwrap_or_default();
+
+        GuestStay {
+            row: row_num.to_string(),
+            guest_name: format!("{} {}", cell(self.first_name), cell(self.surname)),
+            check_in: self.dates.parse(&cell(self.check_in)),
+            check_out: self.dates.parse(&cell(self.check_out)),
+        }
+    }
+}
+
+// Converts a zero based column position to its A1 letter, e.g. 0 -> A, 27 -> AB
+pub fn column_letter(index: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = index + 1;
+    while n > 0 {
+        let rem = (n - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        n = (n - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAD: [&str; 12] = ["Timestamp", "Purpose of stay", "Check-in date", "Check-out date", "Last name", "First name",
+                              "Date of birth", "Citizenship", "Passport number", "Visa number", "Address abroad", "Full name"];
+
+    // The lead guest's columns followed by the question groups of the given guest numbers
+    fn header(groups: &[u32]) -> Vec<String> {
+        let mut header: Vec<String> = LEAD.map(str::to_string).to_vec();
+        for n in groups {
+            for question in ["last name", "first name", "date of birth", "citizenship", "passport number"] {
+                header.push(format!("Guest {} {}", n, question));
+            }
+        }
+        header
+    }
+
+    fn mapping(max_guests: u32) -> ColumnMapping {
+        ColumnMapping {
+            companions: Some(CompanionMapping {
+                max_guests,
+                surname: String::from("Guest {n} last name"),
+                first_name: String::from("Guest {n} first name"),
+                birth_date: String::from("Guest {n} date of birth"),
+                country_of_citizenship: String::from("Guest {n} citizenship"),
+                travel_doc_number: String::from("Guest {n} passport number"),
+                visa_number: None,
+                address_abroad: None,
+                mrz: None,
+            }),
+            ..ColumnMapping::default()
+        }
+    }
+
+    fn guest_numbers(columns: &Columns) -> Vec<u32> {
+        columns.companions.iter().map(|companion| companion.guest_number).collect()
+    }
+
+    #[test]
+    fn finds_headers_ignoring_case_and_whitespace() {
+        let header = header(&[]);
+        assert_eq!(Columns::find(&header, " last NAME ").unwrap(), 4);
+        assert!(matches!(Columns::find(&header, "Surname"), Err(CheckinError::Config(_))));
+        assert_eq!(Columns::find_optional(&header, "Surname").unwrap(), None);
+    }
+
+    #[test]
+    fn companion_groups_stop_at_the_first_missing_group() {
+        let columns = Columns::from_header(&header(&[2, 3, 5]), &mapping(10)).unwrap();
+        assert_eq!(guest_numbers(&columns), [2, 3]);
+    }
+
+    #[test]
+    fn companion_groups_stop_at_max_guests() {
+        let columns = Columns::from_header(&header(&[2, 3, 4]), &mapping(3)).unwrap();
+        assert_eq!(guest_numbers(&columns), [2, 3]);
+    }
+
+    #[test]
+    fn broken_companion_groups_are_config_errors() {
+        // Guest 3's last name question twice
+        let mut duplicated = header(&[2, 3]);
+        duplicated.push(String::from("Guest 3 last name"));
+        assert!(matches!(Columns::from_header(&duplicated, &mapping(10)), Err(CheckinError::Config(_))));
+
+        // Guest 2 without a first name question
+        let incomplete: Vec<String> = header(&[2]).into_iter().filter(|h| h != "Guest 2 first name").collect();
+        assert!(matches!(Columns::from_header(&incomplete, &mapping(10)), Err(CheckinError::Config(_))));
+
+        let mut no_placeholder = mapping(10);
+        no_placeholder.companions.as_mut().unwrap().surname = String::from("Guest last name");
+        assert!(matches!(Columns::from_header(&header(&[2]), &no_placeholder), Err(CheckinError::Config(_))));
+    }
+
+    #[test]
+    fn builds_guests_from_filled_groups() {
+        let columns = Columns::from_header(&header(&[2, 3]), &mapping(10)).unwrap();
+        let mut row: Vec<String> = ["01.06.2024 10:00:00", "10", "01.06.2024", "05.06.2024", "Schmidt", "Anna", "14.03.1985",
+                                    "DEU", "C01X00T47", "", "Hauptstrasse 1, Berlin", "Anna Schmidt"]
+            .map(str::to_string).to_vec();
+        row.extend(["Schmidt", "Paul", "02.02.2015", "DEU", "C01X00T48"].map(str::to_string));
+        // Guest 3's group is left empty
+
+        let guests = columns.guests_from_row("7", &row);
+        assert_eq!(guests.len(), 2);
+        assert_eq!((guests[0].guest_number, guests[0].first_name.as_str()), (1, "Anna"));
+        assert_eq!((guests[1].guest_number, guests[1].first_name.as_str()), (2, "Paul"));
+        assert_eq!(guests[1].row, "7");
+        assert_eq!(guests[1].full_name, "Paul Schmidt");
+        // Companions without an address column get the lead's address and stay dates
+        assert_eq!(guests[1].address_abroad, "Hauptstrasse 1, Berlin");
+        assert_eq!(guests[1].check_out.to_string(), "05.06.2024");
+    }
+
+    #[test]
+    fn column_letters() {
+        let letters: Vec<String> = [0, 12, 25, 26, 27, 701, 702].into_iter().map(column_letter).collect();
+        assert_eq!(letters, ["A", "M", "Z", "AA", "AB", "ZZ", "AAA"]);
+    }
+}
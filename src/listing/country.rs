@@ -0,0 +1,422 @@
+// Nationality codebook for Ubyport. Answers are matched against the three letter
+// code Ubyport expects, the ISO alpha-2 code, English and Czech names and common
+// variants, ignoring case, diacritics and punctuation.
+
+pub struct Country {
+    pub code: &'static str,
+    pub alpha2: &'static str,
+    pub name: &'static str,
+    pub name_cs: &'static str,
+    pub variants: &'static [&'static str],
+}
+
+const fn country(code: &'static str, alpha2: &'static str, name: &'static str, name_cs: &'static str,
+                 variants: &'static [&'static str]) -> Country {
+    Country { code, alpha2, name, name_cs, variants }
+}
+
+static COUNTRIES: &[Country] = &[
+    country("AFG", "AF", "Afghanistan", "Afghánistán", &["Afghan"]),
+    country("ALA", "AX", "Åland Islands", "Ålandy", &[]),
+    country("ALB", "AL", "Albania", "Albánie", &["Albanian"]),
+    country("DZA", "DZ", "Algeria", "Alžírsko", &["Algerian"]),
+    country("ASM", "AS", "American Samoa", "Americká Samoa", &[]),
+    country("AND", "AD", "Andorra", "Andorra", &[]),
+    country("AGO", "AO", "Angola", "Angola", &[]),
+    country("AIA", "AI", "Anguilla", "Anguilla", &[]),
+    country("ATA", "AQ", "Antarctica", "Antarktida", &[]),
+    country("ATG", "AG", "Antigua and Barbuda", "Antigua a Barbuda", &[]),
+    country("ARG", "AR", "Argentina", "Argentina", &["Argentine", "Argentinian"]),
+    country("ARM", "AM", "Armenia", "Arménie", &["Armenian"]),
+    country("ABW", "AW", "Aruba", "Aruba", &[]),
+    country("AUS", "AU", "Australia", "Austrálie", &["Australian"]),
+    country("AUT", "AT", "Austria", "Rakousko", &["Austrian", "Österreich"]),
+    country("AZE", "AZ", "Azerbaijan", "Ázerbájdžán", &["Azerbaijani"]),
+    country("BHS", "BS", "Bahamas", "Bahamy", &[]),
+    country("BHR", "BH", "Bahrain", "Bahrajn", &[]),
+    country("BGD", "BD", "Bangladesh", "Bangladéš", &["Bangladeshi"]),
+    country("BRB", "BB", "Barbados", "Barbados", &[]),
+    country("BLR", "BY", "Belarus", "Bělorusko", &["Belarusian", "Byelorussia"]),
+    country("BEL", "BE", "Belgium", "Belgie", &["Belgian", "België", "Belgique"]),
+    country("BLZ", "BZ", "Belize", "Belize", &[]),
+    country("BEN", "BJ", "Benin", "Benin", &[]),
+    country("BMU", "BM", "Bermuda", "Bermudy", &[]),
+    country("BTN", "BT", "Bhutan", "Bhútán", &[]),
+    country("BOL", "BO", "Bolivia", "Bolívie", &["Bolivian", "Bolivia, Plurinational State of"]),
+    country("BES", "BQ", "Bonaire, Sint Eustatius and Saba", "Karibské Nizozemsko", &["Caribbean Netherlands"]),
+    country("BIH", "BA", "Bosnia and Herzegovina", "Bosna a Hercegovina", &["Bosnia", "Bosnian"]),
+    country("BWA", "BW", "Botswana", "Botswana", &[]),
+    country("BVT", "BV", "Bouvet Island", "Bouvetův ostrov", &[]),
+    country("BRA", "BR", "Brazil", "Brazílie", &["Brazilian", "Brasil"]),
+    country("IOT", "IO", "British Indian Ocean Territory", "Britské indickooceánské území", &[]),
+    country("VGB", "VG", "British Virgin Islands", "Britské Panenské ostrovy", &["Virgin Islands, British"]),
+    country("BRN", "BN", "Brunei", "Brunej", &["Brunei Darussalam"]),
+    country("BGR", "BG", "Bulgaria", "Bulharsko", &["Bulgarian"]),
+    country("BFA", "BF", "Burkina Faso", "Burkina Faso", &[]),
+    country("BDI", "BI", "Burundi", "Burundi", &[]),
+    country("CPV", "CV", "Cabo Verde", "Kapverdy", &["Cape Verde"]),
+    country("KHM", "KH", "Cambodia", "Kambodža", &["Cambodian"]),
+    country("CMR", "CM", "Cameroon", "Kamerun", &[]),
+    country("CAN", "CA", "Canada", "Kanada", &["Canadian"]),
+    country("CYM", "KY", "Cayman Islands", "Kajmanské ostrovy", &[]),
+    country("CAF", "CF", "Central African Republic", "Středoafrická republika", &[]),
+    country("TCD", "TD", "Chad", "Čad", &[]),
+    country("CHL", "CL", "Chile", "Chile", &["Chilean"]),
+    country("CHN", "CN", "China", "Čína", &["Chinese", "People's Republic of China", "PRC"]),
+    country("CXR", "CX", "Christmas Island", "Vánoční ostrov", &[]),
+    country("CCK", "CC", "Cocos (Keeling) Islands", "Kokosové ostrovy", &["Cocos Islands"]),
+    country("COL", "CO", "Colombia", "Kolumbie", &["Colombian"]),
+    country("COM", "KM", "Comoros", "Komory", &[]),
+    country("COG", "CG", "Congo", "Kongo", &["Republic of the Congo", "Congo-Brazzaville"]),
+    country("COD", "CD", "Democratic Republic of the Congo", "Demokratická republika Kongo", &["DR Congo", "DRC", "Congo-Kinshasa", "Congo, The Democratic Republic of the"]),
+    country("COK", "CK", "Cook Islands", "Cookovy ostrovy", &[]),
+    country("CRI", "CR", "Costa Rica", "Kostarika", &[]),
+    country("CIV", "CI", "Côte d'Ivoire", "Pobřeží slonoviny", &["Ivory Coast"]),
+    country("HRV", "HR", "Croatia", "Chorvatsko", &["Croatian", "Hrvatska"]),
+    country("CUB", "CU", "Cuba", "Kuba", &["Cuban"]),
+    country("CUW", "CW", "Curaçao", "Curaçao", &[]),
+    country("CYP", "CY", "Cyprus", "Kypr", &["Cypriot"]),
+    country("CZE", "CZ", "Czechia", "Česko", &["Czech Republic", "Česká republika", "Czech"]),
+    country("DNK", "DK", "Denmark", "Dánsko", &["Danish", "Danmark"]),
+    country("DJI", "DJ", "Djibouti", "Džibutsko", &[]),
+    country("DMA", "DM", "Dominica", "Dominika", &[]),
+    country("DOM", "DO", "Dominican Republic", "Dominikánská republika", &[]),
+    country("ECU", "EC", "Ecuador", "Ekvádor", &["Ecuadorian"]),
+    country("EGY", "EG", "Egypt", "Egypt", &["Egyptian"]),
+    country("SLV", "SV", "El Salvador", "Salvador", &[]),
+    country("GNQ", "GQ", "Equatorial Guinea", "Rovníková Guinea", &[]),
+    country("ERI", "ER", "Eritrea", "Eritrea", &[]),
+    country("EST", "EE", "Estonia", "Estonsko", &["Estonian", "Eesti"]),
+    country("SWZ", "SZ", "Eswatini", "Eswatini", &["Swaziland", "Svazijsko"]),
+    country("ETH", "ET", "Ethiopia", "Etiopie", &["Ethiopian"]),
+    country("FLK", "FK", "Falkland Islands", "Falklandy", &[]),
+    country("FRO", "FO", "Faroe Islands", "Faerské ostrovy", &[]),
+    country("FJI", "FJ", "Fiji", "Fidži", &[]),
+    country("FIN", "FI", "Finland", "Finsko", &["Finnish", "Suomi"]),
+    country("FRA", "FR", "France", "Francie", &["French"]),
+    country("GUF", "GF", "French Guiana", "Francouzská Guyana", &[]),
+    country("PYF", "PF", "French Polynesia", "Francouzská Polynésie", &[]),
+    country("ATF", "TF", "French Southern Territories", "Francouzská jižní a antarktická území", &[]),
+    country("GAB", "GA", "Gabon", "Gabon", &[]),
+    country("GMB", "GM", "Gambia", "Gambie", &[]),
+    country("GEO", "GE", "Georgia", "Gruzie", &["Georgian"]),
+    country("DEU", "DE", "Germany", "Německo", &["German", "Deutschland"]),
+    country("GHA", "GH", "Ghana", "Ghana", &["Ghanaian"]),
+    country("GIB", "GI", "Gibraltar", "Gibraltar", &[]),
+    country("GRC", "GR", "Greece", "Řecko", &["Greek", "Hellas"]),
+    country("GRL", "GL", "Greenland", "Grónsko", &[]),
+    country("GRD", "GD", "Grenada", "Grenada", &[]),
+    country("GLP", "GP", "Guadeloupe", "Guadeloupe", &[]),
+    country("GUM", "GU", "Guam", "Guam", &[]),
+    country("GTM", "GT", "Guatemala", "Guatemala", &[]),
+    country("GGY", "GG", "Guernsey", "Guernsey", &[]),
+    country("GIN", "GN", "Guinea", "Guinea", &[]),
+    country("GNB", "GW", "Guinea-Bissau", "Guinea-Bissau", &[]),
+    country("GUY", "GY", "Guyana", "Guyana", &[]),
+    country("HTI", "HT", "Haiti", "Haiti", &[]),
+    country("HMD", "HM", "Heard Island and McDonald Islands", "Heardův ostrov a McDonaldovy ostrovy", &[]),
+    country("VAT", "VA", "Holy See", "Vatikán", &["Vatican", "Vatican City"]),
+    country("HND", "HN", "Honduras", "Honduras", &[]),
+    country("HKG", "HK", "Hong Kong", "Hongkong", &[]),
+    country("HUN", "HU", "Hungary", "Maďarsko", &["Hungarian", "Magyarország"]),
+    country("ISL", "IS", "Iceland", "Island", &["Icelandic"]),
+    country("IND", "IN", "India", "Indie", &["Indian"]),
+    country("IDN", "ID", "Indonesia", "Indonésie", &["Indonesian"]),
+    country("IRN", "IR", "Iran", "Írán", &["Iranian", "Iran, Islamic Republic of"]),
+    country("IRQ", "IQ", "Iraq", "Irák", &["Iraqi"]),
+    country("IRL", "IE", "Ireland", "Irsko", &["Irish", "Éire"]),
+    country("IMN", "IM", "Isle of Man", "Man", &[]),
+    country("ISR", "IL", "Israel", "Izrael", &["Israeli"]),
+    country("ITA", "IT", "Italy", "Itálie", &["Italian", "Italia"]),
+    country("JAM", "JM", "Jamaica", "Jamajka", &[]),
+    country("JPN", "JP", "Japan", "Japonsko", &["Japanese"]),
+    country("JEY", "JE", "Jersey", "Jersey", &[]),
+    country("JOR", "JO", "Jordan", "Jordánsko", &["Jordanian"]),
+    country("KAZ", "KZ", "Kazakhstan", "Kazachstán", &["Kazakh"]),
+    country("KEN", "KE", "Kenya", "Keňa", &["Kenyan"]),
+    country("KIR", "KI", "Kiribati", "Kiribati", &[]),
+    country("PRK", "KP", "North Korea", "Severní Korea", &["Democratic People's Republic of Korea", "Korea, Democratic People's Republic of", "DPRK"]),
+    country("KOR", "KR", "South Korea", "Jižní Korea", &["Republic of Korea", "Korea, Republic of", "Korea", "Korean"]),
+    country("XKX", "XK", "Kosovo", "Kosovo", &[]),
+    country("KWT", "KW", "Kuwait", "Kuvajt", &[]),
+    country("KGZ", "KG", "Kyrgyzstan", "Kyrgyzstán", &[]),
+    country("LAO", "LA", "Laos", "Laos", &["Lao People's Democratic Republic"]),
+    country("LVA", "LV", "Latvia", "Lotyšsko", &["Latvian", "Latvija"]),
+    country("LBN", "LB", "Lebanon", "Libanon", &["Lebanese"]),
+    country("LSO", "LS", "Lesotho", "Lesotho", &[]),
+    country("LBR", "LR", "Liberia", "Libérie", &[]),
+    country("LBY", "LY", "Libya", "Libye", &[]),
+    country("LIE", "LI", "Liechtenstein", "Lichtenštejnsko", &[]),
+    country("LTU", "LT", "Lithuania", "Litva", &["Lithuanian", "Lietuva"]),
+    country("LUX", "LU", "Luxembourg", "Lucembursko", &[]),
+    country("MAC", "MO", "Macao", "Macao", &["Macau"]),
+    country("MDG", "MG", "Madagascar", "Madagaskar", &[]),
+    country("MWI", "MW", "Malawi", "Malawi", &[]),
+    country("MYS", "MY", "Malaysia", "Malajsie", &["Malaysian"]),
+    country("MDV", "MV", "Maldives", "Maledivy", &[]),
+    country("MLI", "ML", "Mali", "Mali", &[]),
+    country("MLT", "MT", "Malta", "Malta", &["Maltese"]),
+    country("MHL", "MH", "Marshall Islands", "Marshallovy ostrovy", &[]),
+    country("MTQ", "MQ", "Martinique", "Martinik", &[]),
+    country("MRT", "MR", "Mauritania", "Mauritánie", &[]),
+    country("MUS", "MU", "Mauritius", "Mauricius", &[]),
+    country("MYT", "YT", "Mayotte", "Mayotte", &[]),
+    country("MEX", "MX", "Mexico", "Mexiko", &["Mexican", "México"]),
+    country("FSM", "FM", "Micronesia", "Mikronésie", &["Micronesia, Federated States of"]),
+    country("MDA", "MD", "Moldova", "Moldavsko", &["Moldovan", "Moldova, Republic of"]),
+    country("MCO", "MC", "Monaco", "Monako", &[]),
+    country("MNG", "MN", "Mongolia", "Mongolsko", &["Mongolian"]),
+    country("MNE", "ME", "Montenegro", "Černá Hora", &["Montenegrin", "Crna Gora"]),
+    country("MSR", "MS", "Montserrat", "Montserrat", &[]),
+    country("MAR", "MA", "Morocco", "Maroko", &["Moroccan"]),
+    country("MOZ", "MZ", "Mozambique", "Mosambik", &[]),
+    country("MMR", "MM", "Myanmar", "Myanmar", &["Burma", "Barma"]),
+    country("NAM", "NA", "Namibia", "Namibie", &[]),
+    country("NRU", "NR", "Nauru", "Nauru", &[]),
+    country("NPL", "NP", "Nepal", "Nepál", &["Nepali", "Nepalese"]),
+    country("NLD", "NL", "Netherlands", "Nizozemsko", &["Dutch", "Holland", "Holandsko", "Nederland"]),
+    country("NCL", "NC", "New Caledonia", "Nová Kaledonie", &[]),
+    country("NZL", "NZ", "New Zealand", "Nový Zéland", &["New Zealander"]),
+    country("NIC", "NI", "Nicaragua", "Nikaragua", &[]),
+    country("NER", "NE", "Niger", "Niger", &[]),
+    country("NGA", "NG", "Nigeria", "Nigérie", &["Nigerian"]),
+    country("NIU", "NU", "Niue", "Niue", &[]),
+    country("NFK", "NF", "Norfolk Island", "Norfolk", &[]),
+    country("MKD", "MK", "North Macedonia", "Severní Makedonie", &["Macedonia", "Makedonie", "Macedonian"]),
+    country("MNP", "MP", "Northern Mariana Islands", "Severní Mariany", &[]),
+    country("NOR", "NO", "Norway", "Norsko", &["Norwegian", "Norge"]),
+    country("OMN", "OM", "Oman", "Omán", &[]),
+    country("PAK", "PK", "Pakistan", "Pákistán", &["Pakistani"]),
+    country("PLW", "PW", "Palau", "Palau", &[]),
+    country("PSE", "PS", "Palestine", "Palestina", &["Palestinian"]),
+    country("PAN", "PA", "Panama", "Panama", &[]),
+    country("PNG", "PG", "Papua New Guinea", "Papua Nová Guinea", &[]),
+    country("PRY", "PY", "Paraguay", "Paraguay", &[]),
+    country("PER", "PE", "Peru", "Peru", &["Peruvian"]),
+    country("PHL", "PH", "Philippines", "Filipíny", &["Filipino", "Philippine"]),
+    country("PCN", "PN", "Pitcairn", "Pitcairnovy ostrovy", &[]),
+    country("POL", "PL", "Poland", "Polsko", &["Polish", "Polska"]),
+    country("PRT", "PT", "Portugal", "Portugalsko", &["Portuguese"]),
+    country("PRI", "PR", "Puerto Rico", "Portoriko", &[]),
+    country("QAT", "QA", "Qatar", "Katar", &[]),
+    country("REU", "RE", "Réunion", "Réunion", &[]),
+    country("ROU", "RO", "Romania", "Rumunsko", &["Romanian", "România"]),
+    country("RUS", "RU", "Russia", "Rusko", &["Russian Federation", "Ruská federace", "Russian"]),
+    country("RWA", "RW", "Rwanda", "Rwanda", &[]),
+    country("BLM", "BL", "Saint Barthélemy", "Svatý Bartoloměj", &[]),
+    country("SHN", "SH", "Saint Helena", "Svatá Helena", &[]),
+    country("KNA", "KN", "Saint Kitts and Nevis", "Svatý Kryštof a Nevis", &[]),
+    country("LCA", "LC", "Saint Lucia", "Svatá Lucie", &[]),
+    country("MAF", "MF", "Saint Martin", "Svatý Martin", &[]),
+    country("SPM", "PM", "Saint Pierre and Miquelon", "Saint-Pierre a Miquelon", &[]),
+    country("VCT", "VC", "Saint Vincent and the Grenadines", "Svatý Vincenc a Grenadiny", &[]),
+    country("WSM", "WS", "Samoa", "Samoa", &[]),
+    country("SMR", "SM", "San Marino", "San Marino", &[]),
+    country("STP", "ST", "Sao Tome and Principe", "Svatý Tomáš a Princův ostrov", &[]),
+    country("SAU", "SA", "Saudi Arabia", "Saúdská Arábie", &["Saudi"]),
+    country("SEN", "SN", "Senegal", "Senegal", &[]),
+    country("SRB", "RS", "Serbia", "Srbsko", &["Serbian", "Srbija"]),
+    country("SYC", "SC", "Seychelles", "Seychely", &[]),
+    country("SLE", "SL", "Sierra Leone", "Sierra Leone", &[]),
+    country("SGP", "SG", "Singapore", "Singapur", &["Singaporean"]),
+    country("SXM", "SX", "Sint Maarten", "Svatý Martin (Nizozemsko)", &[]),
+    country("SVK", "SK", "Slovakia", "Slovensko", &["Slovak Republic", "Slovak", "Slovenská republika"]),
+    country("SVN", "SI", "Slovenia", "Slovinsko", &["Slovenian", "Slovenija"]),
+    country("SLB", "SB", "Solomon Islands", "Šalamounovy ostrovy", &[]),
+    country("SOM", "SO", "Somalia", "Somálsko", &[]),
+    country("ZAF", "ZA", "South Africa", "Jihoafrická republika", &["South African", "RSA", "JAR"]),
+    country("SGS", "GS", "South Georgia and the South Sandwich Islands", "Jižní Georgie a Jižní Sandwichovy ostrovy", &[]),
+    country("SSD", "SS", "South Sudan", "Jižní Súdán", &[]),
+    country("ESP", "ES", "Spain", "Španělsko", &["Spanish", "España"]),
+    country("LKA", "LK", "Sri Lanka", "Srí Lanka", &[]),
+    country("SDN", "SD", "Sudan", "Súdán", &[]),
+    country("SUR", "SR", "Suriname", "Surinam", &[]),
+    country("SJM", "SJ", "Svalbard and Jan Mayen", "Špicberky a Jan Mayen", &[]),
+    country("SWE", "SE", "Sweden", "Švédsko", &["Swedish", "Sverige"]),
+    country("CHE", "CH", "Switzerland", "Švýcarsko", &["Swiss", "Schweiz", "Suisse", "Svizzera"]),
+    country("SYR", "SY", "Syria", "Sýrie", &["Syrian", "Syrian Arab Republic"]),
+    country("TWN", "TW", "Taiwan", "Tchaj-wan", &["Taiwanese", "Taiwan, Province of China"]),
+    country("TJK", "TJ", "Tajikistan", "Tádžikistán", &[]),
+    country("TZA", "TZ", "Tanzania", "Tanzanie", &["Tanzania, United Republic of"]),
+    country("THA", "TH", "Thailand", "Thajsko", &["Thai"]),
+    country("TLS", "TL", "Timor-Leste", "Východní Timor", &["East Timor"]),
+    country("TGO", "TG", "Togo", "Togo", &[]),
+    country("TKL", "TK", "Tokelau", "Tokelau", &[]),
+    country("TON", "TO", "Tonga", "Tonga", &[]),
+    country("TTO", "TT", "Trinidad and Tobago", "Trinidad a Tobago", &[]),
+    country("TUN", "TN", "Tunisia", "Tunisko", &["Tunisian"]),
+    country("TUR", "TR", "Türkiye", "Turecko", &["Turkey", "Turkish"]),
+    country("TKM", "TM", "Turkmenistan", "Turkmenistán", &[]),
+    country("TCA", "TC", "Turks and Caicos Islands", "Turks a Caicos", &[]),
+    country("TUV", "TV", "Tuvalu", "Tuvalu", &[]),
+    country("UGA", "UG", "Uganda", "Uganda", &[]),
+    country("UKR", "UA", "Ukraine", "Ukrajina", &["Ukrainian", "Україна"]),
+    country("ARE", "AE", "United Arab Emirates", "Spojené arabské emiráty", &["UAE", "SAE", "Emirates"]),
+    country("GBR", "GB", "United Kingdom", "Spojené království", &["UK", "Great Britain", "Britain", "British", "England", "Scotland", "Wales", "Northern Ireland", "Velká Británie", "Anglie"]),
+    country("USA", "US", "United States", "Spojené státy", &["United States of America", "America", "American", "Spojené státy americké"]),
+    country("UMI", "UM", "United States Minor Outlying Islands", "Menší odlehlé ostrovy USA", &[]),
+    country("URY", "UY", "Uruguay", "Uruguay", &[]),
+    country("VIR", "VI", "U.S. Virgin Islands", "Americké Panenské ostrovy", &["Virgin Islands, U.S."]),
+    country("UZB", "UZ", "Uzbekistan", "Uzbekistán", &["Uzbek"]),
+    country("VUT", "VU", "Vanuatu", "Vanuatu", &[]),
+    country("VEN", "VE", "Venezuela", "Venezuela", &["Venezuelan", "Venezuela, Bolivarian Republic of"]),
+    country("VNM", "VN", "Vietnam", "Vietnam", &["Viet Nam", "Vietnamese"]),
+    country("WLF", "WF", "Wallis and Futuna", "Wallis a Futuna", &[]),
+    country("ESH", "EH", "Western Sahara", "Západní Sahara", &[]),
+    country("YEM", "YE", "Yemen", "Jemen", &[]),
+    country("ZMB", "ZM", "Zambia", "Zambie", &[]),
+    country("ZWE", "ZW", "Zimbabwe", "Zimbabwe", &[]),
+    country("XXA", "", "Stateless", "Bez státní příslušnosti", &["None", "Stateless person"]),
+];
+
+// Looks up a citizenship answer. Answers like "DEU - Germany" are taken by their
+// code, and answers like "Czechia (Czech Republic)" only when every part names the
+// same country. An unknown answer gives the closest country, if any.
+pub fn resolve(value: &str) -> Result<&'static Country, Option<&'static Country>> {
+    let lookup = |part: &str| COUNTRIES.iter().find(|c| c.keys().any(|key| normalize(key) == part));
+
+    let whole = normalize(value);
+    if let Some(country) = lookup(&whole) {
+        return Ok(country);
+    }
+
+    if let Some((code, _)) = value.split_once(" - ") {
+        let code = normalize(code);
+        let by_code = COUNTRIES.iter()
+            .find(|c| [c.code, c.alpha2].iter().any(|key| !key.is_empty() && normalize(key) == code));
+        if let Some(country) = by_code {
+            return Ok(country);
+        }
+    }
+
+    // "Korea, Democratic People's Republic of" must not resolve to Korea
+    let matches: Vec<Option<&'static Country>> = value.split(['/', '(', ')', ','])
+        .flat_map(|part| part.split(" - "))
+        .map(normalize)
+        .filter(|part| !part.is_empty())
+        .map(|part| lookup(&part))
+        .collect();
+    match matches.first() {
+        Some(Some(country)) if matches.iter().all(|other| other.is_some_and(|other| other.code == country.code)) => Ok(country),
+        _ => Err(suggest(&whole).or_else(|| matches.into_iter().flatten().next())),
+    }
+}
+
+impl Country {
+    // Every spelling an answer may use for this country
+    fn keys(&self) -> impl Iterator<Item = &'static str> {
+        [self.code, self.alpha2, self.name, self.name_cs]
+            .into_iter()
+            .chain(self.variants.iter().copied())
+            .filter(|key| !key.is_empty())
+    }
+}
+
+// Closest country by edit distance to any of its names, when close enough to be a typo
+fn suggest(value: &str) -> Option<&'static Country> {
+    if value.chars().count() < 3 {
+        return None;
+    }
+
+    COUNTRIES.iter()
+        .filter_map(|c| c.keys()
+            .filter(|key| key.chars().count() > 3)
+            .map(|key| levenshtein(value, &normalize(key)))
+            .min()
+            .map(|distance| (distance, c)))
+        .filter(|(distance, _)| *distance <= (value.chars().count() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+// Lower case without diacritics, punctuation or repeated spaces
+pub fn normalize(value: &str) -> String {
+    let folded: String = value.chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+            'č' | 'ç' | 'ć' => 'c',
+            'ď' => 'd',
+            'é' | 'ě' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ľ' | 'ĺ' | 'ł' => 'l',
+            'ň' | 'ñ' | 'ń' => 'n',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' => 'o',
+            'ř' | 'ŕ' => 'r',
+            'š' | 'ś' | 'ş' => 's',
+            'ť' | 'ţ' => 't',
+            'ú' | 'ů' | 'ù' | 'û' | 'ü' => 'u',
+            'ý' | 'ÿ' => 'y',
+            'ž' | 'ź' | 'ż' => 'z',
+            c => c,
+        })
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || *c == '-')
+        .map(|c| if c == '-' { ' ' } else { c })
+        .collect();
+
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(value: &str) -> Result<&'static str, Option<&'static str>> {
+        resolve(value).map(|c| c.code).map_err(|suggestion| suggestion.map(|c| c.code))
+    }
+
+    #[test]
+    fn resolves_codes_names_and_aliases() {
+        assert_eq!(code("DEU"), Ok("DEU"));
+        assert_eq!(code("de"), Ok("DEU"));
+        assert_eq!(code("Germany"), Ok("DEU"));
+        assert_eq!(code("německo"), Ok("DEU"));
+        assert_eq!(code("  CZECH republic. "), Ok("CZE"));
+        assert_eq!(code("Česko"), Ok("CZE"));
+        assert_eq!(code("Ivory Coast"), Ok("CIV"));
+        assert_eq!(code("Cote d'Ivoire"), Ok("CIV"));
+    }
+
+    #[test]
+    fn resolves_dropdown_answers() {
+        assert_eq!(code("DEU - Germany"), Ok("DEU"));
+        assert_eq!(code("Czechia (Czech Republic)"), Ok("CZE"));
+        assert_eq!(code("Korea, Democratic People's Republic of"), Ok("PRK"));
+        assert_eq!(code("Korea, Republic of"), Ok("KOR"));
+        assert_eq!(code("Congo, The Democratic Republic of the"), Ok("COD"));
+        assert_eq!(code("Virgin Islands, British"), Ok("VGB"));
+    }
+
+    #[test]
+    fn parts_naming_different_countries_are_not_resolved() {
+        assert_eq!(code("Germany / France"), Err(Some("DEU")));
+        assert_eq!(code("Korea, North"), Err(Some("KOR")));
+        assert_eq!(code("XYZ - Germany"), Err(Some("DEU")));
+    }
+
+    #[test]
+    fn unresolved_answers_suggest_a_close_country() {
+        assert_eq!(code("Germny"), Err(Some("DEU")));
+        assert_eq!(code("Slovakai"), Err(Some("SVK")));
+        assert_eq!(code("Atlantis"), Err(None));
+        assert_eq!(code("XY"), Err(None));
+        assert_eq!(code(""), Err(None));
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use log::info;
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::guest::Guest;
+use crate::listing::sidecar::{self, StateFile};
+use crate::listing::source::ReservationSource;
+use crate::listing::status::StatusUpdate;
+use crate::settings::ColumnMapping;
+
+
+// Guest form responses exported to a local CSV file, with registration state kept in a sidecar file
+pub struct CsvReservation {
+    filepath: String,
+    state_filepath: String,
+    column_mapping: ColumnMapping,
+    retry_exported: bool,
+    // Fingerprints of the rows last read, stored with their new states
+    fingerprints: HashMap<String, String>,
+}
+
+impl CsvReservation {
+    pub fn new(filepath: &str, column_mapping: &ColumnMapping, retry_exported: bool) -> Self {
+        CsvReservation {
+            filepath: filepath.to_string(),
+            state_filepath: StateFile::path_for(filepath),
+            column_mapping: column_mapping.clone(),
+            retry_exported,
+            fingerprints: HashMap::new(),
+        }
+    }
+
+    fn read_rows(&self) -> Result<Vec<Vec<String>>, CheckinError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&self.filepath)?;
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for record in reader.records() {
+            rows.push(record?.iter().map(str::to_string).collect());
+        }
+        Ok(rows)
+    }
+}
+
+#[async_trait]
+impl ReservationSource for CsvReservation {
+    // Finds guests in the CSV export that have not been registered in Ubyport
+    async fn find_pending_guests(&mut self) -> Result<Vec<Guest>, CheckinError> {
+        // Rows are numbered as in a spreadsheet, the header being row 1
+        let unregistered_guests = sidecar::find_pending_guests(
+            self.read_rows()?,
+            1,
+            &self.column_mapping,
+            &self.state_filepath,
+            self.retry_exported,
+            &mut self.fingerprints,
+        )?;
+
+        info!("{} unregistered guests found in {}", unregistered_guests.len(), self.filepath);
+
+        Ok(unregistered_guests)
+    }
+
+    async fn find_stays(&mut self) -> Result<Vec<GuestStay>, CheckinError> {
+        sidecar::find_stays(self.read_rows()?, 1, &self.column_mapping)
+    }
+
+    // Records the new statuses in the sidecar state file with a single write
+    async fn mark_guest_states(&self, updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError> {
+        sidecar::mark_guest_states(&self.filepath, &self.state_filepath, &self.fingerprints, updates, run_id)
+    }
+}
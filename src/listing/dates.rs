@@ -0,0 +1,279 @@
+use std::fmt;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+
+// Locales writing numeric dates month first, e.g. 6/1/2024 for 1 June
+const MONTH_FIRST_REGIONS: [&str; 11] = ["us", "ph", "fm", "mh", "pw", "as", "gu", "mp", "pr", "um", "vi"];
+
+// Time of day formats of form timestamps, 24 hour or with AM/PM
+const TIME_FORMATS: [&str; 4] = ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"];
+
+// Text date formats tried when an answer is not all numbers
+const TEXT_FORMATS: [&str; 8] = ["%d %B %Y", "%d %b %Y", "%d. %B %Y", "%d. %b %Y", "%B %d, %Y", "%b %d, %Y", "%B %d %Y", "%b %d %Y"];
+
+// Serial number of 31.12.2099, the last date read from a Sheets serial
+const MAX_SERIAL: i64 = 73_050;
+
+// Sheets serial numbers count days from this date
+fn sheets_epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1899, 12, 30).expect("Invalid Sheets epoch")
+}
+
+// Why a date answer could not be read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DateError {
+    Empty,
+    Unreadable,
+    // Day and month could be either way round and the form has no locale to decide
+    Ambiguous(NaiveDate, NaiveDate),
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateError::Empty => write!(f, "missing"),
+            DateError::Unreadable => write!(f, "not a date"),
+            DateError::Ambiguous(a, b) => write!(f, "ambiguous, {} or {}", a.format("%d.%m.%Y"), b.format("%d.%m.%Y")),
+        }
+    }
+}
+
+// A date answer of the form, as typed and as read
+#[derive(Clone, Debug)]
+pub struct FormDate {
+    pub raw: String,
+    pub date: Result<NaiveDate, DateError>,
+}
+
+impl FormDate {
+    pub fn get(&self) -> Option<NaiveDate> {
+        self.date.as_ref().ok().copied()
+    }
+}
+
+// Written as dd.mm.yyyy once read, otherwise as typed
+impl fmt::Display for FormDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.date {
+            Ok(date) => write!(f, "{}", date.format("%d.%m.%Y")),
+            Err(_) => write!(f, "{}", self.raw),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DateOrder {
+    DayFirst,
+    MonthFirst,
+}
+
+// Reads date answers in the formats guests type and the Sheets API returns:
+// dd.mm.yyyy and other numeric dates with . / - or spaces, yyyy-mm-dd, two digit
+// years, English month names and Sheets serial numbers. Dotted dates are always
+// day first; for other numeric dates the form locale decides the order when day
+// and month could be swapped.
+#[derive(Clone, Debug)]
+pub struct DateParser {
+    order: Option<DateOrder>,
+}
+
+impl DateParser {
+    pub fn new(form_locale: Option<&str>) -> Self {
+        let order = form_locale.filter(|locale| !locale.trim().is_empty()).map(|locale| {
+            let region = locale.trim().to_lowercase().replace('_', "-").rsplit('-').next().unwrap_or_default().to_string();
+            if MONTH_FIRST_REGIONS.contains(&region.as_str()) {
+                DateOrder::MonthFirst
+            } else {
+                DateOrder::DayFirst
+            }
+        });
+
+        DateParser { order }
+    }
+
+    // Stay dates; two digit years are read as 20yy
+    pub fn parse(&self, raw: &str) -> FormDate {
+        self.read(raw, false)
+    }
+
+    // Birth dates; two digit years in the future are read as 19yy
+    pub fn parse_birth_date(&self, raw: &str) -> FormDate {
+        self.read(raw, true)
+    }
+
+    // Form timestamps, e.g. 01.06.2024 14:30:00 or 6/1/2024 2:30:00 PM, read with the
+    // same date rules as answers. Serials carry the time as a fraction of the day.
+    pub fn parse_timestamp(&self, raw: &str) -> Option<NaiveDateTime> {
+        let value = raw.trim();
+        if let Some(date) = Self::from_serial(value) {
+            let seconds = (value.parse::<f64>().ok()?.fract() * 86_400.0).round() as i64;
+            return Some(date.and_time(NaiveTime::MIN) + Duration::seconds(seconds));
+        }
+
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let time_start = words.iter().position(|word| word.contains(':')).unwrap_or(words.len());
+        let date = self.read(&words[..time_start].join(" "), false).date.ok()?;
+        let time = match &words[time_start..] {
+            [] => NaiveTime::MIN,
+            time => {
+                let time = time.join(" ");
+                TIME_FORMATS.iter().find_map(|format| NaiveTime::parse_from_str(&time, format).ok())?
+            },
+        };
+        Some(date.and_time(time))
+    }
+
+    fn read(&self, raw: &str, in_past: bool) -> FormDate {
+        let value = raw.trim();
+        // Drop a time of day, e.g. from a date time cell
+        let value = match value.rsplit_once(' ') {
+            Some((date, time)) if time.contains(':') => date.trim(),
+            _ => value,
+        };
+
+        let date = if value.is_empty() {
+            Err(DateError::Empty)
+        } else if let Some(date) = Self::from_serial(value) {
+            Ok(date)
+        } else if value.chars().all(|c| c.is_ascii_digit() || ". /-".contains(c)) {
+            self.read_numbers(value, in_past)
+        } else {
+            TEXT_FORMATS.iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .ok_or(DateError::Unreadable)
+        };
+
+        FormDate { raw: raw.trim().to_string(), date }
+    }
+
+    // Unformatted Sheets date cells are days since 30.12.1899, with the time as a
+    // fraction. Only five digit numbers, 1927 to 2099, are taken as serials, so a
+    // date typed without separators such as 01062024 is not read as the year 4800.
+    fn from_serial(value: &str) -> Option<NaiveDate> {
+        let (days, fraction) = value.split_once('.').unwrap_or((value, "0"));
+        if days.len() < 5 || !days.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let days = value.parse::<f64>().ok()?.trunc() as i64;
+        (10_000..=MAX_SERIAL).contains(&days).then(|| sheets_epoch() + Duration::days(days))
+    }
+
+    fn read_numbers(&self, value: &str, in_past: bool) -> Result<NaiveDate, DateError> {
+        let parts: Vec<&str> = value.split(|c: char| !c.is_ascii_digit()).filter(|p| !p.is_empty()).collect();
+        let [a, b, c] = parts[..] else { return Err(DateError::Unreadable) };
+        let number = |part: &str| part.parse::<u32>().map_err(|_| DateError::Unreadable);
+
+        // Year first is always yyyy-mm-dd
+        if a.len() == 4 {
+            return NaiveDate::from_ymd_opt(number(a)? as i32, number(b)?, number(c)?).ok_or(DateError::Unreadable);
+        }
+
+        let year = match c.len() {
+            4 => number(c)? as i32,
+            2 => Self::expand_year(number(c)? as i32, in_past),
+            _ => return Err(DateError::Unreadable),
+        };
+        let (a, b) = (number(a)?, number(b)?);
+        let day_first = NaiveDate::from_ymd_opt(year, b, a);
+        let month_first = NaiveDate::from_ymd_opt(year, a, b);
+
+        match (day_first, month_first) {
+            (Some(date), None) | (None, Some(date)) => Ok(date),
+            (None, None) => Err(DateError::Unreadable),
+            (Some(date), Some(_)) if a == b => Ok(date),
+            (Some(date), Some(other)) => match (value.contains('.'), self.order) {
+                (true, _) | (false, Some(DateOrder::DayFirst)) => Ok(date),
+                (false, Some(DateOrder::MonthFirst)) => Ok(other),
+                (false, None) => Err(DateError::Ambiguous(date, other)),
+            },
+        }
+    }
+
+    fn expand_year(year: i32, in_past: bool) -> i32 {
+        let year = 2000 + year;
+        if in_past && year > Utc::now().year() {
+            year - 100
+        } else {
+            year
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn dotted_dates_are_day_first() {
+        let parser = DateParser::new(Some("en_US"));
+        assert_eq!(parser.parse("01.06.2024").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("1. 6. 2024").date, Ok(date(2024, 6, 1)));
+    }
+
+    #[test]
+    fn ambiguous_day_and_month_follow_the_locale() {
+        assert_eq!(DateParser::new(Some("en_US")).parse("6/1/2024").date, Ok(date(2024, 6, 1)));
+        assert_eq!(DateParser::new(Some("en-GB")).parse("6/1/2024").date, Ok(date(2024, 1, 6)));
+        assert_eq!(DateParser::new(Some("cs")).parse("6/1/2024").date, Ok(date(2024, 1, 6)));
+        assert_eq!(
+            DateParser::new(None).parse("6/1/2024").date,
+            Err(DateError::Ambiguous(date(2024, 1, 6), date(2024, 6, 1))),
+        );
+    }
+
+    #[test]
+    fn unambiguous_dates_ignore_the_locale() {
+        assert_eq!(DateParser::new(None).parse("13/6/2024").date, Ok(date(2024, 6, 13)));
+        assert_eq!(DateParser::new(Some("en_US")).parse("13/6/2024").date, Ok(date(2024, 6, 13)));
+        assert_eq!(DateParser::new(None).parse("6/6/2024").date, Ok(date(2024, 6, 6)));
+        assert_eq!(DateParser::new(None).parse("2024-06-01").date, Ok(date(2024, 6, 1)));
+        assert_eq!(DateParser::new(None).parse("31/31/2024").date, Err(DateError::Unreadable));
+    }
+
+    #[test]
+    fn two_digit_years() {
+        let parser = DateParser::new(None);
+        assert_eq!(parser.parse("01.06.24").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("01.06.85").date, Ok(date(2085, 6, 1)));
+        assert_eq!(parser.parse_birth_date("01.06.85").date, Ok(date(1985, 6, 1)));
+        assert_eq!(parser.parse_birth_date("01.06.05").date, Ok(date(2005, 6, 1)));
+    }
+
+    #[test]
+    fn sheets_serials() {
+        let parser = DateParser::new(None);
+        assert_eq!(parser.parse("45444").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("45444.5833").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("73050").date, Ok(date(2099, 12, 31)));
+        assert_eq!(parser.parse("73051").date, Err(DateError::Unreadable));
+        assert_eq!(parser.parse("01062024").date, Err(DateError::Unreadable));
+    }
+
+    #[test]
+    fn timestamps() {
+        let time = |date: NaiveDate, h: u32, m: u32, s: u32| date.and_hms_opt(h, m, s).unwrap();
+        let parser = DateParser::new(Some("en_US"));
+        assert_eq!(parser.parse_timestamp("01.06.2024 14:30:05"), Some(time(date(2024, 6, 1), 14, 30, 5)));
+        assert_eq!(parser.parse_timestamp("6/1/2024 14:30:05"), Some(time(date(2024, 6, 1), 14, 30, 5)));
+        assert_eq!(parser.parse_timestamp("6/1/2024 2:30:05 PM"), Some(time(date(2024, 6, 1), 14, 30, 5)));
+        assert_eq!(parser.parse_timestamp("45444.75"), Some(time(date(2024, 6, 1), 18, 0, 0)));
+        assert_eq!(parser.parse_timestamp("2024-06-01"), Some(time(date(2024, 6, 1), 0, 0, 0)));
+        assert_eq!(DateParser::new(None).parse_timestamp("6/1/2024 14:30:05"), None);
+        assert_eq!(parser.parse_timestamp("01.06.2024 25:00"), None);
+    }
+
+    #[test]
+    fn text_dates_and_times() {
+        let parser = DateParser::new(None);
+        assert_eq!(parser.parse("1 June 2024").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("Jun 1, 2024").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("01.06.2024 14:30:00").date, Ok(date(2024, 6, 1)));
+        assert_eq!(parser.parse("  ").date, Err(DateError::Empty));
+        assert_eq!(parser.parse("soon").date, Err(DateError::Unreadable));
+    }
+}
@@ -0,0 +1,120 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use log::info;
+use crate::listing::dates::DateParser;
+use crate::listing::guest::Guest;
+
+
+// Marks older form submissions of the same guest and stay as superseded by the
+// newest one. Pending rows are the same submission when their lead guests' travel
+// document number and birth date match and the stays overlap. Companions share
+// the fate of their row. Timestamps are read in the form's locale.
+pub fn mark_duplicates(guests: &mut [Guest], dates: &DateParser) {
+    // Newest submission first, by form timestamp and then by row
+    let mut newest_first: Vec<usize> = (0..guests.len()).filter(|&i| guests[i].guest_number == 1).collect();
+    newest_first.sort_by_key(|&i| Reverse(submission_order(&guests[i], dates)));
+
+    let mut kept: Vec<usize> = Vec::new();
+    for i in newest_first {
+        match kept.iter().find(|&&k| is_duplicate(&guests[k], &guests[i])) {
+            Some(&k) => {
+                info!("Row {} ({} {}) is an older submission of row {}",
+                    guests[i].row, guests[i].first_name, guests[i].surname, guests[k].row);
+                guests[i].superseded_by = Some(guests[k].row.clone());
+            },
+            None => kept.push(i),
+        }
+    }
+
+    let superseded: HashMap<String, String> = guests.iter()
+        .filter_map(|guest| Some((guest.row.clone(), guest.superseded_by.clone()?)))
+        .collect();
+    for guest in guests.iter_mut().filter(|guest| guest.guest_number > 1) {
+        guest.superseded_by = superseded.get(&guest.row).cloned();
+    }
+}
+
+// Rows without a readable timestamp count as older than rows with one
+fn submission_order(guest: &Guest, dates: &DateParser) -> (Option<NaiveDateTime>, u32) {
+    (
+        dates.parse_timestamp(&guest.timestamp),
+        guest.row.parse().unwrap_or(0),
+    )
+}
+
+fn is_duplicate(a: &Guest, b: &Guest) -> bool {
+    let doc_number = |guest: &Guest| guest.get_travel_doc_number()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    !doc_number(a).is_empty()
+        && doc_number(a) == doc_number(b)
+        && a.birth_date.to_string() == b.birth_date.to_string()
+        && stays_overlap(a, b)
+}
+
+// Stays sharing at least one night, or starting the same day. Dates that do not
+// parse only match when they are written the same.
+fn stays_overlap(a: &Guest, b: &Guest) -> bool {
+    match (a.check_in.get(), a.check_out.get(), b.check_in.get(), b.check_out.get()) {
+        (Some(a_in), Some(a_out), Some(b_in), Some(b_out)) => (a_in < b_out && b_in < a_out) || a_in == b_in,
+        _ => a.check_in.raw == b.check_in.raw,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guest(row: &str, timestamp: &str, check_in: &str, passport: &str) -> Guest {
+        let dates = DateParser::new(Some("en-US"));
+        Guest::new(&row.to_string(), timestamp.to_string(), Err(String::from("10")), dates.parse(check_in), dates.parse("6/20/2024"),
+                   String::from("Schmidt"), String::from("Anna"), dates.parse_birth_date("3/14/1985"), String::from("DEU"),
+                   passport.to_string(), String::new(), String::new(), String::from("Anna Schmidt"))
+    }
+
+    fn superseded(guests: &[Guest]) -> Vec<Option<&str>> {
+        guests.iter().map(|guest| guest.superseded_by.as_deref()).collect()
+    }
+
+    #[test]
+    fn newest_submission_wins() {
+        let dates = DateParser::new(Some("en-US"));
+        // Row 2 was sent last, on 6/10 in the afternoon
+        let mut guests = [
+            guest("2", "6/10/2024 2:15:00 PM", "6/15/2024", "C01X00T47"),
+            guest("3", "6/10/2024 9:00:00", "6/15/2024", "c01x 00t47"),
+            guest("4", "6/2/2024 18:00:00", "6/16/2024", "C01X00T47"),
+        ];
+        mark_duplicates(&mut guests, &dates);
+        assert_eq!(superseded(&guests), [None, Some("2"), Some("2")]);
+    }
+
+    #[test]
+    fn rows_break_ties_and_unreadable_timestamps_count_as_older() {
+        let dates = DateParser::new(Some("en-US"));
+        let mut guests = [
+            guest("2", "6/10/2024 9:00:00", "6/15/2024", "C01X00T47"),
+            guest("3", "6/10/2024 9:00:00", "6/15/2024", "C01X00T47"),
+            guest("4", "yesterday", "6/15/2024", "C01X00T47"),
+        ];
+        mark_duplicates(&mut guests, &dates);
+        assert_eq!(superseded(&guests), [Some("3"), None, Some("3")]);
+    }
+
+    #[test]
+    fn other_stays_and_documents_are_kept() {
+        let dates = DateParser::new(Some("en-US"));
+        let mut guests = [
+            guest("2", "6/10/2024 9:00:00", "6/15/2024", "C01X00T47"),
+            guest("3", "6/11/2024 9:00:00", "6/25/2024", "C01X00T47"),
+            guest("4", "6/12/2024 9:00:00", "6/15/2024", "C01X00T48"),
+        ];
+        mark_duplicates(&mut guests, &dates);
+        assert_eq!(superseded(&guests), [None, None, None]);
+    }
+}
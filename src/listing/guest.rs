@@ -1,54 +1,234 @@
 
 use std::fmt;
 use std::error::Error;
-use chrono::{Datelike, NaiveDate, Utc};
 use log::warn;
+use crate::listing::country;
+use crate::listing::dates::{DateError, FormDate};
+use crate::listing::mrz::Mrz;
+use crate::unlfile::unmappable_characters;
+use crate::listing::purpose::PurposeOfStay;
+use crate::listing::status::RegistrationStatus;
+use crate::listing::translit::transliterate;
 
 #[derive(Clone, Debug)]
 pub struct Guest {
     pub row: String,
+    // 1 for the lead guest of a form response, 2 and up for companions on the same row
+    pub guest_number: u32,
     pub timestamp: String,
-    pub purpose_of_stay: String,
-    pub check_in: String,
-    pub check_out: String,
+    // The unrecognised form answer when it maps to no purpose of stay code
+    pub purpose_of_stay: Result<PurposeOfStay, String>,
+    pub check_in: FormDate,
+    pub check_out: FormDate,
     pub surname: String,
     pub first_name: String,
-    pub birth_date: String,
+    pub birth_date: FormDate,
     pub country_of_citizenship: String,
     travel_doc_number: String,
     visa_number: String,
     pub address_abroad: String,
     pub full_name: String,
-    pub data_errors: Vec<GuestError>
+    // Errors and warnings found in the input, see GuestError::severity
+    pub issues: Vec<GuestError>,
+    // Fields written in Latin letters for the UNL file: field, value as typed and Latin value
+    pub transliterated: Vec<(GuestField, String, String)>,
+    // Row of a newer submission of the same guest and stay, set when merging duplicates
+    pub superseded_by: Option<String>,
+    // Status of the guest's row when it was read
+    pub status: RegistrationStatus,
 }
 
 impl fmt::Display for Guest {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Row: {}, Timestamp: {}, Purpose_of_Stay: {}, Check_In: {}, Check_Out: {}, Surname: {}, First_Name: {}, Birth_Date: {}, Country_of_Citizenship: {}, Address_Abroad: {}, Full_Name: {}",
-               self.row, self.timestamp, self.purpose_of_stay, self.check_in, self.check_out, self.surname, self.first_name, self.birth_date, self.country_of_citizenship, self.address_abroad, self.full_name)
+               self.row, self.timestamp, self.get_purpose_code(), self.check_in, self.check_out, self.surname, self.first_name, self.birth_date, self.country_of_citizenship, self.address_abroad, self.full_name)
     }
 }
 
+// Guest field an issue was found in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuestField {
+    CheckIn,
+    CheckOut,
+    Surname,
+    FirstName,
+    BirthDate,
+    CountryOfCitizenship,
+    TravelDocNumber,
+    VisaNumber,
+    AddressAbroad,
+    PurposeOfStay,
+    Mrz,
+}
+
+impl GuestField {
+    // Identity document numbers are only shown in part
+    fn is_sensitive(&self) -> bool {
+        matches!(self, GuestField::TravelDocNumber | GuestField::VisaNumber | GuestField::Mrz)
+    }
+}
+
+impl fmt::Display for GuestField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuestField::CheckIn => write!(f, "check in date"),
+            GuestField::CheckOut => write!(f, "check out date"),
+            GuestField::Surname => write!(f, "surname"),
+            GuestField::FirstName => write!(f, "first name"),
+            GuestField::BirthDate => write!(f, "date of birth"),
+            GuestField::CountryOfCitizenship => write!(f, "country of citizenship"),
+            GuestField::TravelDocNumber => write!(f, "travel doc number"),
+            GuestField::VisaNumber => write!(f, "visa number"),
+            GuestField::AddressAbroad => write!(f, "address abroad"),
+            GuestField::PurposeOfStay => write!(f, "purpose of stay"),
+            GuestField::Mrz => write!(f, "passport MRZ"),
+        }
+    }
+}
+
+// Why a field was rejected, with a stable code for anything reading issues downstream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    Missing,
+    TooShort,
+    TooLong,
+    BadFormat,
+    Ambiguous,
+    // Not in a codebook such as nationalities or purposes of stay
+    UnknownCode,
+    // Fine alone but contradicting another field
+    Inconsistent,
+    CheckDigit,
+    MrzMismatch,
+    Required,
+    NotNeeded,
+    // Characters the UNL file's Windows-1250 encoding can not hold
+    Unencodable,
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::Missing => "missing",
+            ErrorCode::TooShort => "too_short",
+            ErrorCode::TooLong => "too_long",
+            ErrorCode::BadFormat => "bad_format",
+            ErrorCode::Ambiguous => "ambiguous",
+            ErrorCode::UnknownCode => "unknown_code",
+            ErrorCode::Inconsistent => "inconsistent",
+            ErrorCode::CheckDigit => "check_digit",
+            ErrorCode::MrzMismatch => "mrz_mismatch",
+            ErrorCode::Required => "required",
+            ErrorCode::NotNeeded => "not_needed",
+            ErrorCode::Unencodable => "unencodable",
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            ErrorCode::Missing => "is missing",
+            ErrorCode::TooShort => "is too short",
+            ErrorCode::TooLong => "is too long",
+            ErrorCode::BadFormat => "could not be read",
+            ErrorCode::Ambiguous => "is ambiguous",
+            ErrorCode::UnknownCode => "matches no known code",
+            ErrorCode::Inconsistent => "is inconsistent",
+            ErrorCode::CheckDigit => "has a wrong check digit",
+            ErrorCode::MrzMismatch => "differs from the passport MRZ",
+            ErrorCode::Required => "is required",
+            ErrorCode::NotNeeded => "is not needed",
+            ErrorCode::Unencodable => "can not be written to the UNL file",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    // Keeps the guest's row from being exported
+    Error,
+    // Reported, the guest is exported anyway
+    Warning,
+}
+
 #[derive(Clone, Debug)]
-pub enum GuestError {
-    InvalidInput(String),
+pub struct GuestError {
+    pub field: GuestField,
+    pub code: ErrorCode,
+    // The offending answer, redacted for identity document numbers, None when empty
+    pub value: Option<String>,
+    pub severity: Severity,
+    // What the host should know to fix it, e.g. the closest known country
+    pub detail: Option<String>,
 }
 
 impl fmt::Display for GuestError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            GuestError::InvalidInput(field) => write!(f, "Invalid input provided for {}", field),
+        write!(f, "{}", self.field)?;
+        if let Some(value) = &self.value {
+            write!(f, " \"{}\"", value)?;
+        }
+        write!(f, " {}", self.code.describe())?;
+        if let Some(detail) = &self.detail {
+            write!(f, ", {}", detail)?;
         }
+        Ok(())
+    }
+}
+
+impl GuestError {
+    pub fn new(field: GuestField, code: ErrorCode, value: &str) -> Self {
+        let value = value.trim();
+        GuestError {
+            field,
+            code,
+            value: match value.chars().count() {
+                0 => None,
+                _ if field.is_sensitive() => Some(Self::redact(value)),
+                _ => Some(value.to_string()),
+            },
+            severity: Severity::Error,
+            detail: None,
+        }
+    }
+
+    pub fn warning(field: GuestField, code: ErrorCode, value: &str) -> Self {
+        GuestError { severity: Severity::Warning, ..Self::new(field, code, value) }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn is_warning(&self) -> bool {
+        self.severity == Severity::Warning
+    }
+
+    // All but the last 3 characters masked
+    fn redact(value: &str) -> String {
+        let len = value.chars().count();
+        value.chars()
+            .enumerate()
+            .map(|(i, c)| if len <= 3 || i + 3 < len { '*' } else { c })
+            .collect()
     }
 }
 
 impl Error for GuestError {}
 
 impl Guest {
-    pub fn new(row: &String, timestamp: String, purpose_of_stay: String, check_in: String, check_out: String, surname: String, first_name: String, birth_date: String,
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(row: &String, timestamp: String, purpose_of_stay: Result<PurposeOfStay, String>, check_in: FormDate, check_out: FormDate, surname: String, first_name: String, birth_date: FormDate,
                country_of_citizenship: String, travel_doc_number: String, visa_number: String, address_abroad: String, full_name: String) -> Self {
         let mut guest = Guest {
             row: row.to_string(),
+            guest_number: 1,
             timestamp,
             purpose_of_stay,
             check_in,
@@ -61,9 +241,13 @@ impl Guest {
             visa_number,
             address_abroad,
             full_name,
-            data_errors: Vec::new(),
+            issues: Vec::new(),
+            transliterated: Vec::new(),
+            superseded_by: None,
+            status: RegistrationStatus::Pending,
         };
 
+        guest.transliterate();
         guest.check_input_format();
 
         guest
@@ -80,151 +264,238 @@ impl Guest {
             self.address_abroad,
             self.travel_doc_number,
             self.visa_number,
-            self.purpose_of_stay
+            self.get_purpose_code()
         }
     }
    
     fn check_input_format(&mut self) {
+        let issues: Vec<GuestError> = [
+            self.check_format_check_in(),
+            self.check_format_check_out(),
+            self.check_format_surname(),
+            self.check_format_first_name(),
+            self.check_format_dob(),
+            self.check_format_country_of_citizenship(),
+            self.check_format_address_abroad(),
+            self.check_format_travel_doc_number(),
+            self.check_format_visa_number(),
+            self.check_format_purpose_of_stay(),
+        ]
+        .into_iter()
+        .filter_map(Result::err)
+        .chain(self.check_encoding())
+        .collect();
 
-        if let Err(e) = self.check_format_check_in() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        }; 
-        if let Err(e) = self.check_format_check_out() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_surname() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_first_name() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_dob() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_country_of_citizenship() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_address_abroad() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_travel_doc_number() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_visa_number() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
-        if let Err(e) = self.check_format_purpose_of_stay() {
-            warn!("Row {}, {} {}: {}", self.row, self.first_name, self.surname, e);
-            self.data_errors.push(e)
-        };
+        for issue in issues {
+            self.add_issue(issue);
+        }
     }
     
+    pub fn get_travel_doc_number(&self) -> &str { &self.travel_doc_number }
+    pub fn get_visa_number(&self) -> &str { &self.visa_number }
+
+    // Records an issue with the guest's input, logged as it is found
+    pub fn add_issue(&mut self, issue: GuestError) {
+        warn!("Row {}, {} {}: {}", self.get_row_label(), self.first_name, self.surname, issue);
+        self.issues.push(issue);
+    }
+
+    // Whether an error, not just warnings, keeps the guest from being exported
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| !issue.is_warning())
+    }
+
+    pub fn has_issue(&self, field: GuestField, code: ErrorCode) -> bool {
+        self.issues.iter().any(|issue| issue.field == field && issue.code == code)
+    }
+
+    pub fn has_warnings(&self) -> bool {
+        self.issues.iter().any(GuestError::is_warning)
+    }
+
     pub fn get_data_errors(&self) -> String {
-        if !self.data_errors.is_empty() {
-            let mut msg = String::from("Input error found in field(s): ");
-            
-            for(index, error) in self.data_errors.iter().enumerate() {
-                match error {
-                    GuestError::InvalidInput(e) => {
-                        if index == self.data_errors.len() - 1 {
-                            msg.push_str(&format!("{}", e));
-                        } else {
-                            msg.push_str(&format!("{}, ", e));
-                        }
-                    },
-                };
-            }
+        Self::join_issues(self.issues.iter().filter(|issue| !issue.is_warning()))
+    }
+
+    pub fn get_data_warnings(&self) -> String {
+        Self::join_issues(self.issues.iter().filter(|issue| issue.is_warning()))
+    }
+
+    fn join_issues<'a>(issues: impl Iterator<Item = &'a GuestError>) -> String {
+        issues.map(GuestError::to_string).collect::<Vec<_>>().join("; ")
+    }
 
-            return msg
+    // Cross-checks the form against the machine readable zone of the guest's travel document
+    pub fn check_mrz(&mut self, text: &str) {
+        let mut issues = Vec::new();
+        match Mrz::parse(text) {
+            Err(e) => issues.push(GuestError::new(GuestField::Mrz, ErrorCode::BadFormat, text).with_detail(e.to_string())),
+            Ok(mrz) => {
+                for field in &mrz.failed_checks {
+                    issues.push(GuestError::new(GuestField::Mrz, ErrorCode::CheckDigit, text).with_detail(format!("in the {}", field)));
+                }
+
+                let typed_number: String = self.travel_doc_number.chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .collect::<String>()
+                    .to_uppercase();
+                if typed_number != mrz.document_number {
+                    issues.push(GuestError::new(GuestField::TravelDocNumber, ErrorCode::MrzMismatch, &self.travel_doc_number));
+                }
+                if !mrz.name_matches(&mrz.surname, &self.surname, false) {
+                    issues.push(GuestError::new(GuestField::Surname, ErrorCode::MrzMismatch, &self.surname)
+                        .with_detail(format!("which has {}", mrz.surname)));
+                }
+                if !mrz.name_matches(&mrz.given_names, &self.first_name, true) {
+                    issues.push(GuestError::new(GuestField::FirstName, ErrorCode::MrzMismatch, &self.first_name)
+                        .with_detail(format!("which has {}", mrz.given_names)));
+                }
+                if self.birth_date.get().is_some_and(|date| !mrz.birth_date_matches(date)) {
+                    issues.push(GuestError::new(GuestField::BirthDate, ErrorCode::MrzMismatch, &self.birth_date.to_string())
+                        .with_detail(format!("which has {} (YYMMDD)", mrz.birth_date)));
+                }
+                // Compared once citizenship has been resolved to a code
+                if !self.has_issue(GuestField::CountryOfCitizenship, ErrorCode::UnknownCode) && mrz.nationality_code() != self.country_of_citizenship {
+                    issues.push(GuestError::new(GuestField::CountryOfCitizenship, ErrorCode::MrzMismatch, &self.country_of_citizenship)
+                        .with_detail(format!("which has {}", mrz.nationality_code())));
+                }
+            },
         }
 
-        String::from("No guest input errors found")
+        for issue in issues {
+            self.add_issue(issue);
+        }
     }
 
-    fn check_format_check_in(&self) -> Result<(), GuestError> {
-        if !NaiveDate::parse_from_str(&self.check_in, "%d.%m.%Y").is_ok() {
-            return Err(GuestError::InvalidInput(String::from("check in date")));
+    // Ubyport code of the purpose of stay, empty when the answer was not recognised
+    pub fn get_purpose_code(&self) -> &str {
+        self.purpose_of_stay.as_ref().map(PurposeOfStay::code).unwrap_or_default()
+    }
+
+    // Row of the guest in the report, naming companions by their guest number
+    pub fn get_row_label(&self) -> String {
+        match self.guest_number {
+            1 => self.row.clone(),
+            n => format!("{}, guest {}", self.row, n),
         }
-        Ok(())
+    }
+
+    // Names and address typed in a script Windows-1250 can not carry, such as
+    // Cyrillic or Greek, are written in Latin letters, keeping the original
+    fn transliterate(&mut self) {
+        for (field, value) in [
+            (GuestField::Surname, &mut self.surname),
+            (GuestField::FirstName, &mut self.first_name),
+            (GuestField::AddressAbroad, &mut self.address_abroad),
+        ] {
+            let latin = transliterate(value);
+            if latin != *value {
+                let original = std::mem::replace(value, latin.clone());
+                self.transliterated.push((field, original, latin));
+            }
+        }
+    }
+
+    // Free text fields of the U-record must be written in Windows-1250
+    fn check_encoding(&self) -> Vec<GuestError> {
+        [
+            (GuestField::Surname, &self.surname),
+            (GuestField::FirstName, &self.first_name),
+            (GuestField::AddressAbroad, &self.address_abroad),
+            (GuestField::TravelDocNumber, &self.travel_doc_number),
+            (GuestField::VisaNumber, &self.visa_number),
+        ]
+        .into_iter()
+        .filter_map(|(field, value)| {
+            let chars = unmappable_characters(value);
+            // Chinese names are not transliterated, the guest has to type them in pinyin
+            let hint = if chars.iter().any(|c| ('\u{4E00}'..='\u{9FFF}').contains(c)) { ", use pinyin" } else { "" };
+            (!chars.is_empty()).then(|| GuestError::new(field, ErrorCode::Unencodable, value).with_detail(format!(
+                "characters {} not allowed{}",
+                chars.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<_>>().join(", "),
+                hint,
+            )))
+        })
+        .collect()
+    }
+
+    fn check_format_check_in(&self) -> Result<(), GuestError> {
+        Self::check_date(GuestField::CheckIn, &self.check_in)
     }
 
     fn check_format_check_out(&self) -> Result<(), GuestError> {
-        if !NaiveDate::parse_from_str(&self.check_out, "%d.%m.%Y").is_ok() {
-            return Err(GuestError::InvalidInput(String::from("check out date")));
+        Self::check_date(GuestField::CheckOut, &self.check_out)
+    }
+
+    // Names the field with the answer and why it could not be read
+    fn check_date(field: GuestField, date: &FormDate) -> Result<(), GuestError> {
+        match &date.date {
+            Ok(_) => Ok(()),
+            Err(DateError::Empty) => Err(GuestError::new(field, ErrorCode::Missing, "")),
+            Err(DateError::Unreadable) => Err(GuestError::new(field, ErrorCode::BadFormat, &date.raw)),
+            Err(DateError::Ambiguous(a, b)) => Err(GuestError::new(field, ErrorCode::Ambiguous, &date.raw)
+                .with_detail(format!("{} or {}", a.format("%d.%m.%Y"), b.format("%d.%m.%Y")))),
+        }
+    }
+
+    // Length of a free text answer within the U-record's limits, a minimum of 0 allowing it empty
+    fn check_length(field: GuestField, value: &str, min: usize, max: usize) -> Result<(), GuestError> {
+        match value.chars().count() {
+            0 if min > 0 => Err(GuestError::new(field, ErrorCode::Missing, value)),
+            len if len < min => Err(GuestError::new(field, ErrorCode::TooShort, value)
+                .with_detail(format!("at least {} characters", min))),
+            len if len > max => Err(GuestError::new(field, ErrorCode::TooLong, value)
+                .with_detail(format!("at most {} characters", max))),
+            _ => Ok(()),
         }
-        Ok(())
     }
 
     fn check_format_surname(&self) -> Result<(), GuestError> {
-        match self.surname.chars().count() {
-            1..=50 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("surname"))),
-        } 
+        Self::check_length(GuestField::Surname, &self.surname, 1, 50)
     }
 
     fn check_format_first_name(&self) -> Result<(), GuestError> {
-        match self.first_name.chars().count() {
-            0..=24 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("first name"))),
-        } 
+        Self::check_length(GuestField::FirstName, &self.first_name, 0, 24)
     }
 
+    // Only checks the date could be read, StayRules checks it against the stay
     fn check_format_dob(&self) -> Result<(), GuestError> {
-        let format = "%d.%m.%Y";
-        let dob = NaiveDate::parse_from_str(&self.birth_date, format);
-        let current_year = Utc::now().year();
-
-        // Check DOB datetime format
-        if !dob.is_ok() {
-            return Err(GuestError::InvalidInput(String::from("date of birth format incorrect")));
-        // Ensure DOB year is not the current year
-        } else if (dob.unwrap().year() == current_year) {
-            return Err(GuestError::InvalidInput(String::from(format!("date of birth year is {}", current_year))));
-        }
-        Ok(())
+        Self::check_date(GuestField::BirthDate, &self.birth_date)
     }
 
-    fn check_format_country_of_citizenship(&self) -> Result<(), GuestError> {
-        match self.country_of_citizenship.chars().count() {
-            3 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("country of citizenship"))),
-        } 
+    // Replaces the citizenship answer with its code from the nationality codebook
+    fn check_format_country_of_citizenship(&mut self) -> Result<(), GuestError> {
+        match country::resolve(&self.country_of_citizenship) {
+            Ok(country) => {
+                self.country_of_citizenship = country.code.to_string();
+                Ok(())
+            },
+            Err(suggestion) => {
+                let e = GuestError::new(GuestField::CountryOfCitizenship, ErrorCode::UnknownCode, &self.country_of_citizenship);
+                Err(match suggestion {
+                    Some(c) => e.with_detail(format!("did you mean {} ({})?", c.name, c.code)),
+                    None => e,
+                })
+            },
+        }
     }
 
     fn check_format_address_abroad(&self) -> Result<(), GuestError> {
-        match self.address_abroad.chars().count() {
-            0..=255 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("address abroad"))),
-        } 
+        Self::check_length(GuestField::AddressAbroad, &self.address_abroad, 0, 255)
     }
 
     fn check_format_travel_doc_number(&self) -> Result<(), GuestError> {
-        match self.travel_doc_number.chars().count() {
-            6..=30 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("travel doc number"))),
-        } 
+        Self::check_length(GuestField::TravelDocNumber, &self.travel_doc_number, 6, 30)
     }
 
     fn check_format_visa_number(&self) -> Result<(), GuestError> {
-        match self.visa_number.chars().count() {
-            0..=15 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("visa number"))),
-        } 
+        Self::check_length(GuestField::VisaNumber, &self.visa_number, 0, 15)
     }
 
     fn check_format_purpose_of_stay(&self) -> Result<(), GuestError> {
-        match self.purpose_of_stay.chars().count() {
-            2 => Ok(()),
-            _ => return Err(GuestError::InvalidInput(String::from("purpose of stay"))),
-        } 
+        match &self.purpose_of_stay {
+            Ok(_) => Ok(()),
+            Err(answer) => Err(GuestError::new(GuestField::PurposeOfStay, ErrorCode::UnknownCode, answer)),
+        }
     }
 }
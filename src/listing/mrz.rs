@@ -0,0 +1,244 @@
+use std::fmt;
+use chrono::NaiveDate;
+use crate::listing::country::normalize;
+
+
+// Machine readable zone of a travel document (ICAO 9303): TD1 identity cards
+// (3 lines of 30), TD2 documents (2 lines of 36) and TD3 passports (2 lines of 44)
+#[derive(Clone, Debug)]
+pub struct Mrz {
+    pub document_number: String,
+    pub nationality: String,
+    // YYMMDD, the century is not part of the MRZ
+    pub birth_date: String,
+    pub surname: String,
+    pub given_names: String,
+    // Whether the name field was filled to the end, so names may be cut short
+    pub names_truncated: bool,
+    // Fields whose check digit does not match
+    pub failed_checks: Vec<&'static str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MrzError {
+    // Not 2x44, 2x36 or 3x30 characters
+    UnknownLayout(usize),
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for MrzError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MrzError::UnknownLayout(len) => write!(f, "{} characters do not make a 2 or 3 line MRZ", len),
+            MrzError::InvalidCharacter(c) => write!(f, "character '{}' can not be part of an MRZ", c),
+        }
+    }
+}
+
+impl Mrz {
+    // Reads an MRZ pasted or typed with or without line breaks. Spaces are dropped
+    // and « (a common OCR reading of the filler) is taken as <.
+    pub fn parse(text: &str) -> Result<Self, MrzError> {
+        let text: String = text.chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| if c == '«' { '<' } else { c.to_ascii_uppercase() })
+            .collect();
+        if let Some(c) = text.chars().find(|c| !(c.is_ascii_uppercase() || c.is_ascii_digit() || *c == '<')) {
+            return Err(MrzError::InvalidCharacter(c));
+        }
+
+        match text.len() {
+            90 => Ok(Self::parse_td1(&text[0..30], &text[30..60], &text[60..90])),
+            72 => Ok(Self::parse_td2_td3(&text[0..36], &text[36..72])),
+            88 => Ok(Self::parse_td2_td3(&text[0..44], &text[44..88])),
+            len => Err(MrzError::UnknownLayout(len)),
+        }
+    }
+
+    fn parse_td1(line1: &str, line2: &str, line3: &str) -> Self {
+        let mut failed_checks = Vec::new();
+
+        // Document numbers longer than 9 characters continue in the optional data,
+        // followed by their check digit, with < in the usual check digit position
+        let document_number = if &line1[14..15] == "<" {
+            let rest = line1[15..30].trim_end_matches('<');
+            let (more, check) = rest.split_at(rest.len().saturating_sub(1));
+            let number = format!("{}{}", &line1[5..14], more);
+            if !Self::check(&number, check) {
+                failed_checks.push("document number");
+            }
+            number
+        } else {
+            if !Self::check(&line1[5..14], &line1[14..15]) {
+                failed_checks.push("document number");
+            }
+            line1[5..14].to_string()
+        };
+        if !Self::check(&line2[0..6], &line2[6..7]) {
+            failed_checks.push("birth date");
+        }
+        if !Self::check(&line2[8..14], &line2[14..15]) {
+            failed_checks.push("expiry date");
+        }
+        let composite = format!("{}{}{}{}", &line1[5..30], &line2[0..7], &line2[8..15], &line2[18..29]);
+        if !Self::check(&composite, &line2[29..30]) {
+            failed_checks.push("composite");
+        }
+
+        let (surname, given_names, names_truncated) = Self::names(line3);
+        Mrz {
+            document_number: document_number.trim_end_matches('<').to_string(),
+            nationality: line2[15..18].to_string(),
+            birth_date: line2[0..6].to_string(),
+            surname,
+            given_names,
+            names_truncated,
+            failed_checks,
+        }
+    }
+
+    // TD2 and TD3 differ only in line length and the size of the optional data
+    fn parse_td2_td3(line1: &str, line2: &str) -> Self {
+        let mut failed_checks = Vec::new();
+        let last = line2.len() - 1;
+
+        if !Self::check(&line2[0..9], &line2[9..10]) {
+            failed_checks.push("document number");
+        }
+        if !Self::check(&line2[13..19], &line2[19..20]) {
+            failed_checks.push("birth date");
+        }
+        if !Self::check(&line2[21..27], &line2[27..28]) {
+            failed_checks.push("expiry date");
+        }
+        // Only passports carry a check digit over the personal number
+        if line2.len() == 44 && !Self::check(&line2[28..42], &line2[42..43]) {
+            failed_checks.push("personal number");
+        }
+        let composite = format!("{}{}{}", &line2[0..10], &line2[13..20], &line2[21..last]);
+        if !Self::check(&composite, &line2[last..]) {
+            failed_checks.push("composite");
+        }
+
+        let (surname, given_names, names_truncated) = Self::names(&line1[5..]);
+        Mrz {
+            document_number: line2[0..9].trim_end_matches('<').to_string(),
+            nationality: line2[10..13].to_string(),
+            birth_date: line2[13..19].to_string(),
+            surname,
+            given_names,
+            names_truncated,
+            failed_checks,
+        }
+    }
+
+    // SURNAME<<GIVEN<NAMES<<<, with < between the parts of a name
+    fn names(field: &str) -> (String, String, bool) {
+        let (surname, given_names) = field.split_once("<<").unwrap_or((field, ""));
+        let words = |part: &str| part.split('<').filter(|w| !w.is_empty()).collect::<Vec<_>>().join(" ");
+        (words(surname), words(given_names), !field.ends_with('<'))
+    }
+
+    // ICAO 9303 check digit: weights 7, 3, 1 over digits, letters A=10 to Z=35 and < as 0
+    fn check(field: &str, check_digit: &str) -> bool {
+        let sum: u32 = field.chars()
+            .zip([7, 3, 1].into_iter().cycle())
+            .map(|(c, weight)| weight * match c {
+                '0'..='9' => c as u32 - '0' as u32,
+                'A'..='Z' => c as u32 - 'A' as u32 + 10,
+                _ => 0,
+            })
+            .sum();
+        let expected = char::from_digit(sum % 10, 10).unwrap_or('0');
+        // An unused check digit is written as <
+        check_digit.chars().next().map(|c| if c == '<' { '0' } else { c }) == Some(expected)
+    }
+
+    // Three letter nationality, with Germany's single letter D read as DEU
+    pub fn nationality_code(&self) -> &str {
+        match self.nationality.trim_end_matches('<') {
+            "D" => "DEU",
+            code => code,
+        }
+    }
+
+    pub fn birth_date_matches(&self, date: NaiveDate) -> bool {
+        date.format("%y%m%d").to_string() == self.birth_date
+    }
+
+    // Whether a typed name is the one in the MRZ, given as "A B" with the parts
+    // separated by spaces. Diacritics are dropped, or written out the way ICAO
+    // transliterates Ä, Ö, Ü and Å, and a name cut short in the MRZ matches its
+    // start. With first_only, the first of several given names is enough.
+    pub fn name_matches(&self, mrz_name: &str, typed: &str, first_only: bool) -> bool {
+        let mrz_name = mrz_name.to_lowercase();
+        let expanded = typed.to_lowercase()
+            .replace('ä', "ae").replace('ö', "oe").replace('ü', "ue")
+            .replace('å', "aa").replace('æ', "ae").replace('ø', "oe").replace('ß', "ss");
+
+        [normalize(typed), normalize(&expanded)].iter().any(|typed| {
+            *typed == mrz_name
+                || (self.names_truncated && typed.starts_with(&mrz_name))
+                || (first_only && !typed.is_empty() && mrz_name.starts_with(&format!("{} ", typed)))
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Specimen documents of ICAO 9303 for Anna Maria Eriksson of Utopia
+    const TD1: &str = "I<UTOD231458907<<<<<<<<<<<<<<<\n7408122F1204159UTO<<<<<<<<<<<6\nERIKSSON<<ANNA<MARIA<<<<<<<<<<";
+    const TD2: &str = "I<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<\nD231458907UTO7408122F1204159<<<<<<<6";
+    const TD3: &str = "P<UTOERIKSSON<<ANNA<MARIA<<<<<<<<<<<<<<<<<<<\nL898902C36UTO7408122F1204159ZE184226B<<<<<10";
+
+    fn assert_eriksson(mrz: &Mrz, document_number: &str) {
+        assert_eq!(mrz.failed_checks, Vec::<&str>::new());
+        assert_eq!(mrz.document_number, document_number);
+        assert_eq!(mrz.nationality, "UTO");
+        assert_eq!(mrz.birth_date, "740812");
+        assert_eq!(mrz.surname, "ERIKSSON");
+        assert_eq!(mrz.given_names, "ANNA MARIA");
+        assert!(!mrz.names_truncated);
+    }
+
+    #[test]
+    fn reads_the_icao_specimens() {
+        assert_eriksson(&Mrz::parse(TD1).unwrap(), "D23145890");
+        assert_eriksson(&Mrz::parse(TD2).unwrap(), "D23145890");
+        assert_eriksson(&Mrz::parse(TD3).unwrap(), "L898902C3");
+    }
+
+    #[test]
+    fn reads_long_td1_document_numbers() {
+        let mrz = Mrz::parse("I<UTOD23145890<7349<<<<<<<<<<<7408122F1204159UTO<<<<<<<<<<<6ERIKSSON<<ANNA<MARIA<<<<<<<<<<").unwrap();
+        assert_eriksson(&mrz, "D23145890734");
+    }
+
+    #[test]
+    fn reports_wrong_check_digits() {
+        let mrz = Mrz::parse(&TD3.replace("7408122", "7408132")).unwrap();
+        assert_eq!(mrz.failed_checks, vec!["birth date", "composite"]);
+
+        let mrz = Mrz::parse(&TD1.replace("D231458907", "D231458917")).unwrap();
+        assert_eq!(mrz.failed_checks, vec!["document number", "composite"]);
+    }
+
+    #[test]
+    fn rejects_other_layouts_and_characters() {
+        assert_eq!(Mrz::parse(&TD3[..80]).unwrap_err(), MrzError::UnknownLayout(79));
+        assert_eq!(Mrz::parse(&TD3.replace('Z', "Ž")).unwrap_err(), MrzError::InvalidCharacter('Ž'));
+        assert!(Mrz::parse(&TD3.to_lowercase().replace('<', "«")).is_ok());
+    }
+
+    #[test]
+    fn matches_typed_names_and_dates() {
+        let mrz = Mrz::parse(TD3).unwrap();
+        assert!(mrz.name_matches(&mrz.surname, "Eriksson", false));
+        assert!(mrz.name_matches(&mrz.given_names, "Anna", true));
+        assert!(!mrz.name_matches(&mrz.given_names, "Anna", false));
+        assert!(mrz.birth_date_matches(NaiveDate::from_ymd_opt(1974, 8, 12).unwrap()));
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::error::CheckinError;
+use crate::listing::country::normalize;
+
+
+// Purpose of stay codes accepted by Ubyport
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PurposeOfStay {
+    Medical,
+    Official,
+    Study,
+    Culture,
+    Sport,
+    Business,
+    Visit,
+    Employment,
+    Tourism,
+    Transit,
+    Political,
+    Other,
+}
+
+impl PurposeOfStay {
+    const ALL: [PurposeOfStay; 12] = [
+        PurposeOfStay::Medical,
+        PurposeOfStay::Official,
+        PurposeOfStay::Study,
+        PurposeOfStay::Culture,
+        PurposeOfStay::Sport,
+        PurposeOfStay::Business,
+        PurposeOfStay::Visit,
+        PurposeOfStay::Employment,
+        PurposeOfStay::Tourism,
+        PurposeOfStay::Transit,
+        PurposeOfStay::Political,
+        PurposeOfStay::Other,
+    ];
+
+    // Two digit code written to the U-record
+    pub fn code(&self) -> &'static str {
+        match self {
+            PurposeOfStay::Medical => "00",
+            PurposeOfStay::Official => "01",
+            PurposeOfStay::Study => "02",
+            PurposeOfStay::Culture => "03",
+            PurposeOfStay::Sport => "04",
+            PurposeOfStay::Business => "05",
+            PurposeOfStay::Visit => "06",
+            PurposeOfStay::Employment => "07",
+            PurposeOfStay::Tourism => "10",
+            PurposeOfStay::Transit => "17",
+            PurposeOfStay::Political => "27",
+            PurposeOfStay::Other => "99",
+        }
+    }
+
+    // Accepts "10" as well as "1" for a code
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = format!("{:0>2}", code.trim());
+        Self::ALL.into_iter().find(|purpose| purpose.code() == code)
+    }
+
+    // Built-in English and Czech form labels
+    fn labels(&self) -> &'static [&'static str] {
+        match self {
+            PurposeOfStay::Medical => &["Medical treatment", "Medical", "Léčení", "Lékařské ošetření"],
+            PurposeOfStay::Official => &["Official", "Official visit", "Služební", "Úřední"],
+            PurposeOfStay::Study => &["Study", "Studies", "Studium"],
+            PurposeOfStay::Culture => &["Culture", "Cultural", "Kultura"],
+            PurposeOfStay::Sport => &["Sport", "Sports"],
+            PurposeOfStay::Business => &["Business", "Obchod", "Obchodní", "Podnikání"],
+            PurposeOfStay::Visit => &["Visit", "Visiting family or friends", "Návštěva"],
+            PurposeOfStay::Employment => &["Employment", "Work", "Zaměstnání", "Práce"],
+            PurposeOfStay::Tourism => &["Tourism", "Holiday", "Vacation", "Leisure", "Turistika", "Dovolená"],
+            PurposeOfStay::Transit => &["Transit", "Tranzit"],
+            PurposeOfStay::Political => &["Political", "Politický"],
+            PurposeOfStay::Other => &["Other", "Ostatní", "Jiné"],
+        }
+    }
+}
+
+impl fmt::Display for PurposeOfStay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.labels()[0], self.code())
+    }
+}
+
+// Separators between a code or label and the rest of an answer
+const CODE_SEPARATORS: [&str; 3] = [" - ", " – ", ":"];
+
+// Form answers to purpose of stay codes: the built-in labels, overridden and
+// extended by the listing's purpose_labels
+#[derive(Clone, Debug)]
+pub struct PurposeLabels {
+    labels: HashMap<String, PurposeOfStay>,
+}
+
+impl PurposeLabels {
+    pub fn new(configured: &HashMap<String, String>) -> Result<Self, CheckinError> {
+        let mut labels: HashMap<String, PurposeOfStay> = PurposeOfStay::ALL.into_iter()
+            .flat_map(|purpose| purpose.labels().iter().map(move |label| (normalize(label), purpose)))
+            .collect();
+
+        for (label, code) in configured {
+            let purpose = PurposeOfStay::from_code(code).ok_or_else(|| CheckinError::Config(format!(
+                "Purpose of stay label \"{}\" maps to unknown code \"{}\"", label, code
+            )))?;
+            labels.insert(normalize(label), purpose);
+        }
+
+        Ok(PurposeLabels { labels })
+    }
+
+    // Reads a form answer as a label, a code on its own or before a separator
+    // ("10 - Turistika"), or the parts of a "label - label" or "label / label"
+    // answer when they agree. Unknown answers are returned as they are.
+    pub fn parse(&self, answer: &str) -> Result<PurposeOfStay, String> {
+        let answer = answer.trim();
+        if let Some(purpose) = self.labels.get(&normalize(answer)) {
+            return Ok(*purpose);
+        }
+
+        // A number running into other text, such as "2 weeks", is not a code
+        let code = CODE_SEPARATORS.iter()
+            .find_map(|separator| answer.split_once(separator).map(|(code, _)| code.trim()))
+            .unwrap_or(answer);
+        if !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()) {
+            return PurposeOfStay::from_code(code).ok_or_else(|| answer.to_string());
+        }
+
+        let mut parts = vec![answer];
+        for separator in CODE_SEPARATORS.iter().chain(&["/"]) {
+            parts = parts.iter().flat_map(|part| part.split(separator)).collect();
+        }
+        let mut purposes = parts.iter().filter_map(|part| self.labels.get(&normalize(part)));
+        match purposes.next() {
+            Some(purpose) if purposes.all(|other| other == purpose) => Ok(*purpose),
+            _ => Err(answer.to_string()),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> PurposeLabels {
+        let configured = HashMap::from([("Wedding".to_string(), "6".to_string())]);
+        PurposeLabels::new(&configured).unwrap()
+    }
+
+    #[test]
+    fn reads_labels() {
+        let labels = labels();
+        assert_eq!(labels.parse("Tourism"), Ok(PurposeOfStay::Tourism));
+        assert_eq!(labels.parse(" dovolena "), Ok(PurposeOfStay::Tourism));
+        assert_eq!(labels.parse("Holiday / Dovolená"), Ok(PurposeOfStay::Tourism));
+        assert_eq!(labels.parse("Business - Obchod"), Ok(PurposeOfStay::Business));
+        assert_eq!(labels.parse("wedding"), Ok(PurposeOfStay::Visit));
+    }
+
+    #[test]
+    fn reads_codes_on_their_own_or_before_a_separator() {
+        let labels = labels();
+        assert_eq!(labels.parse("10"), Ok(PurposeOfStay::Tourism));
+        assert_eq!(labels.parse("1"), Ok(PurposeOfStay::Official));
+        assert_eq!(labels.parse("10 - Turistika"), Ok(PurposeOfStay::Tourism));
+        assert_eq!(labels.parse("99: Other"), Ok(PurposeOfStay::Other));
+        assert_eq!(labels.parse("42"), Err("42".to_string()));
+    }
+
+    #[test]
+    fn rejects_numbers_in_other_answers() {
+        let labels = labels();
+        assert_eq!(labels.parse("2 weeks"), Err("2 weeks".to_string()));
+        assert_eq!(labels.parse("1st visit"), Err("1st visit".to_string()));
+        assert_eq!(labels.parse("Holiday / Work"), Err("Holiday / Work".to_string()));
+        assert_eq!(labels.parse(""), Err(String::new()));
+    }
+}
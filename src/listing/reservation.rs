@@ -1,238 +1,383 @@
-use std::collections::HashMap;
+use async_trait::async_trait;
+use google_sheets4::client::NoToken;
 use google_sheets4::oauth2::{read_service_account_key, ServiceAccountAuthenticator};
 use google_sheets4::{hyper::client::HttpConnector, hyper_rustls::HttpsConnector};
 use google_sheets4::{Sheets, hyper, hyper_rustls};
-use google_sheets4::api::ValueRange;
+use google_sheets4::api::{BatchUpdateValuesRequest, ValueRange};
 use serde_json::json;
-use log::{debug, info, warn, error};
+use log::{debug, info, warn};
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::columns::{column_letter, Columns};
+use crate::listing::duplicates;
 use crate::listing::guest::Guest;
+use crate::listing::source::ReservationSource;
+use crate::listing::status::{RegistrationStatus, StatusUpdate};
+use crate::retry::RetryPolicy;
+use crate::settings::{ColumnMapping, SheetsApi, SheetsAuth};
+use chrono::{SecondsFormat, Utc};
+use std::sync::Arc;
 
 
+// Authenticated Google Sheets client, cheap to clone. Clones share the
+// connection pool and the cached access token.
+pub type SheetsHub = Sheets<HttpsConnector<HttpConnector>>;
+
+// Creates a Sheets client for the configured endpoint and auth mode
+pub async fn build_hub(sheets_api: &SheetsApi, service_account_key_path: &str) -> Result<SheetsHub, CheckinError> {
+    let client = hyper::Client::builder()
+        .build(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|e| CheckinError::Config(format!("No TLS root certificates found: {}", e)))?
+        .https_or_http()
+        .enable_http1()
+        .build());
+
+    // Create the Sheets API client
+    let mut hub = match sheets_api.auth {
+        SheetsAuth::ServiceAccount => {
+            // Load service account key file
+            let service_account_key = read_service_account_key(service_account_key_path)
+                .await
+                .map_err(|e| CheckinError::Auth(format!("Failed to read service account key {}: {}", service_account_key_path, e)))?;
+
+            // Create the authenticator
+            let auth = ServiceAccountAuthenticator::builder(service_account_key)
+                .build()
+                .await
+                .map_err(|e| CheckinError::Auth(format!("Failed to create authenticator: {}", e)))?;
+
+            Sheets::new(client, auth)
+        },
+        SheetsAuth::None => Sheets::new(client, NoToken),
+    };
+
+    // Point the client at the configured endpoint
+    if !sheets_api.base_url.ends_with('/') {
+        warn!("Sheets base_url {} should end with '/'", sheets_api.base_url);
+    }
+    hub.base_url(sheets_api.base_url.clone());
+    hub.root_url(sheets_api.base_url.clone());
+
+    Ok(hub)
+}
+
 #[derive(Clone)]
 pub struct Reservation {
     spreadsheet_id: String,
     sheet_name: String,
-    hub: Option<Sheets<HttpsConnector<HttpConnector>>>,
+    column_mapping: ColumnMapping,
+    status_column: Option<usize>,
+    updated_at_column: Option<usize>,
+    run_id_column: Option<usize>,
+    issues_column: Option<usize>,
+    retry_exported: bool,
+    hub: SheetsHub,
+    retry: Arc<RetryPolicy>,
 }
 
 impl Reservation {
-    pub async fn new(spreadsheet_id: &str, sheet_name: &str, service_account_key_filepath: &str) -> Self {
-        let mut res = Reservation {
+    pub fn new(
+        spreadsheet_id: &str,
+        sheet_name: &str,
+        column_mapping: &ColumnMapping,
+        retry_exported: bool,
+        hub: SheetsHub,
+        retry: Arc<RetryPolicy>,
+    ) -> Self {
+        Reservation {
             spreadsheet_id: spreadsheet_id.to_string(),
             sheet_name: sheet_name.to_string(),
-            hub: None,
-        };
-        res.set_hub(service_account_key_filepath).await;
-        res
+            column_mapping: column_mapping.clone(),
+            status_column: None,
+            updated_at_column: None,
+            run_id_column: None,
+            issues_column: None,
+            retry_exported,
+            hub,
+            retry,
+        }
     }
 
-    async fn set_hub(&mut self, service_account_key_path: &str) {
-        
-        // Load service account key file
-        let service_account_key = read_service_account_key(service_account_key_path)
-            .await
-            .expect("Failed to read service account key");
-        
-        // Create the authenticator
-        let auth = ServiceAccountAuthenticator::builder(service_account_key)
-            .build()
-            .await.expect("Failed to create authenticator");
-
-
-        // Create the Sheets API client
-        self.hub = Some(Sheets::new (
-            hyper::Client::builder()
-                .build(hyper_rustls::HttpsConnectorBuilder::new()
-                .with_native_roots()
-                .unwrap()
-                .https_or_http()
-                .enable_http1()
-                .build()), 
-            auth.clone(),
-        ));
+    // Converts sheet cell values to plain strings
+    fn row_to_strings(row: &[serde_json::Value]) -> Vec<String> {
+        row.iter()
+            .map(|val| match val.as_str() {
+                Some(v) => v.to_string(),
+                None => val.to_string(),
+            })
+            .collect()
+    }
+
+    // Gets Google Spreadsheet header row
+    async fn get_header_response(&self) -> Result<Vec<serde_json::Value>, CheckinError> {
+        let sheet_range = &format!("{}!1:1", self.sheet_name);
+        let result = self.retry.run("Reading header row", |mut retry_after| async move {
+            self.hub.spreadsheets()
+                .values_get(&self.spreadsheet_id, sheet_range)
+                .delegate(&mut retry_after)
+                .doit()
+                .await
+        }).await?;
+
+        Ok(result.1.values
+            .and_then(|rows| rows.into_iter().next())
+            .unwrap_or_default())
+    }
+
+    // Find Google Spreadsheet rows with unregistered guests
+    async fn unregistered_guests(&self, timestamp_column: usize, status_column: usize) -> Result<Vec<u32>, CheckinError> {
+        let mut responses = self.get_unregistered_responses(timestamp_column, status_column).await?.into_iter();
+        let timestamps = responses.next().and_then(|response| response.values).unwrap_or_default();
+        let statuses = responses.next().and_then(|response| response.values).unwrap_or_default();
+        Ok(Self::get_unregistered_guests(timestamps.len(), statuses, self.retry_exported))
+    }
+
+    // Gets the timestamp and registration status columns with one batched request. Sheets
+    // leaves out trailing empty cells, so new submissions without a status are only
+    // counted through the timestamp column, which the form always fills.
+    async fn get_unregistered_responses(&self, timestamp_column: usize, status_column: usize) -> Result<Vec<ValueRange>, CheckinError> {
+        let result = self.retry.run("Reading registration status column", |mut retry_after| async move {
+            let mut call = self.hub.spreadsheets().values_batch_get(&self.spreadsheet_id);
+            for column in [timestamp_column, status_column] {
+                let col = column_letter(column);
+                call = call.add_ranges(&format!("{}!{}2:{}", self.sheet_name, col, col));
+            }
+            call.delegate(&mut retry_after).doit().await
+        }).await?;
+
+        Ok(result.1.value_ranges.unwrap_or_default())
+    }
+
+    // Checks "Registered With Authorities" column input for guests that still need exporting.
+    // Rows past the end of the status column have no status yet and are pending.
+    fn get_unregistered_guests(row_count: usize, statuses: Vec<Vec<serde_json::Value>>, retry_exported: bool) -> Vec<u32> {
+        let row_count = row_count.max(statuses.len());
+        info!("Checking a total of {} guests", row_count);
+
+        let mut guests: Vec<u32> = Vec::new();
+        let mut statuses = statuses.into_iter();
+        for i in (2..).take(row_count) {
+            let value = statuses.next()
+                .and_then(|cell| cell.into_iter().next())
+                .map(|v| v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string()))
+                .unwrap_or_default();
+
+            match RegistrationStatus::parse(&value) {
+                Some(status) if status.needs_export(retry_exported) => guests.push(i),
+                Some(_) => (),
+                None => warn!("Unknown registration status \"{}\" on row {}, skipping", value, i),
+            }
+        }
+
+        info!("{} unregistered guests found", guests.len());
+
+        guests
     }
 
-    // Update row (Guest) "Registered With Authorities" in spreadsheet 
-    pub async fn update_registered_with_authorities(&self, row: &str, first_name: &str, last_name: &str) {
-        let mut req = ValueRange::default();
-        let range = format!("{}!M{}", self.sheet_name, row);
-        req.range = Some(range.clone());
-        req.values = Some(vec![vec![json!("TRUE")]]);
-
-        let result = self.hub.clone()
-            .unwrap()
-            .spreadsheets()
-            .values_update(req, &self.spreadsheet_id, &range)
-            .value_input_option("RAW")
-            .doit()
-            .await;
-
-        match result {
-            Ok(response) => {
-                debug!("{:?}", response.1);
-                info!("Updated  {} {} on row {} col 'Registered With Authorities'", first_name, last_name, row );
-            },
-            Err(e) => error!("Updating registration status for guest {} {} on row {}: {}", first_name, last_name, row, e.to_string()),
+    // Gets every row of the Google Spreadsheet
+    async fn get_sheet_response(&self) -> Result<ValueRange, CheckinError> {
+        let result = self.retry.run("Reading guest stays", |mut retry_after| async move {
+            self.hub.spreadsheets()
+                .values_get(&self.spreadsheet_id, &self.sheet_name)
+                .delegate(&mut retry_after)
+                .doit()
+                .await
+        }).await?;
+
+        Ok(result.1)
+    }
+
+    // Gets given rows (guests) from Google Spreadsheet with one batched request.
+    // Value ranges are returned in the same order as the requested rows.
+    async fn get_guest_rows_response(&self, rows: &[u32]) -> Result<Vec<ValueRange>, CheckinError> {
+        if rows.is_empty() {
+            return Ok(Vec::new());
         }
+
+        let result = self.retry.run("Reading guest rows", |mut retry_after| async move {
+            let mut call = self.hub.spreadsheets().values_batch_get(&self.spreadsheet_id);
+            for row in rows {
+                call = call.add_ranges(&format!("{}!{}:{}", self.sheet_name, row, row));
+            }
+            call.delegate(&mut retry_after).doit().await
+        }).await?;
+
+        // Log response
+        debug!("log guest_rows_response: {:?}", result.0);
+        Ok(result.1.value_ranges.unwrap_or_default())
     }
+}
 
+#[async_trait]
+impl ReservationSource for Reservation {
     // Finds guests in Google Spreadsheet that have not been registered in Ubyport
-    pub async fn find_unregistered_guests(&self) -> Vec<Guest> {
-        
+    async fn find_pending_guests(&mut self) -> Result<Vec<Guest>, CheckinError> {
+
+        // Match header row to guest fields
+        let header = Self::row_to_strings(&self.get_header_response().await?);
+        let columns = Columns::from_header(&header, &self.column_mapping)?;
+        let status_column = Columns::find(&header, &self.column_mapping.registered)?;
+        self.status_column = Some(status_column);
+        // Sheets without these columns only get the status written
+        self.updated_at_column = Columns::find_optional(&header, &self.column_mapping.status_updated_at)?;
+        self.run_id_column = Columns::find_optional(&header, &self.column_mapping.run_id)?;
+        if self.updated_at_column.is_none() || self.run_id_column.is_none() {
+            info!("Sheet {} has no \"{}\" or \"{}\" column, status changes are written without them",
+                self.sheet_name, self.column_mapping.status_updated_at, self.column_mapping.run_id);
+        }
+        self.issues_column = match &self.column_mapping.checkin_issues {
+            Some(name) => Some(Columns::find(&header, name)?),
+            None => None,
+        };
+
         // Find row that contain unregistered guests in guest response form
-        let unregistered_guest_row_nums = self.unregistered_guests().await;
+        let unregistered_guest_row_nums = self.unregistered_guests(columns.timestamp, status_column).await?;
         
-        // Get guest row(s) from spreadsheet
-        let unregistered_guest_rows = match &self.hub {
-            Some(_hub) => {
-                self.clone().get_guest_rows_response(unregistered_guest_row_nums.clone()).await
-            },
-            None => panic!("No Google hub found"),
-        };
+        // Get guest row(s) from spreadsheet in a single batched request
+        let unregistered_guest_rows = self.get_guest_rows_response(&unregistered_guest_row_nums).await?;
 
         // Convert to Guest object instances
         let mut unregistered_guests = Vec::new();
-        let mut unregistered_guest_count = 0; 
-        
-        for unregistered_guest in &unregistered_guest_rows {
-            let mut guest_hash: HashMap<String, String> = HashMap::new(); 
-            let row_num = format!("{}", unregistered_guest_row_nums[unregistered_guest_count]);
 
-            // Load row into hash
+        for (row_num, unregistered_guest) in unregistered_guest_row_nums.iter().zip(&unregistered_guest_rows) {
             match &unregistered_guest.values {
-                Some(row) => {
-                    for col in row {
-                        let mut i = 1;
-                        for val in col {
-                            match i {
-                                1  => guest_hash.insert("timestamp".to_string(), val.to_string().trim_matches('"').to_string()),
-                                2  => guest_hash.insert("purpose_of_stay".to_string(), val.to_string().trim_matches('"')[0..2].to_string()),
-                                3  => guest_hash.insert("check_in".to_string(), val.to_string().trim_matches('"').to_string()),
-                                4  => guest_hash.insert("check_out".to_string(), val.to_string().trim_matches('"').to_string()), 
-                                5  => guest_hash.insert("surname".to_string(), val.to_string().trim_matches('"').to_string()),
-                                6  => guest_hash.insert("first_name".to_string(), val.to_string().trim_matches('"').to_string()),
-                                7  => guest_hash.insert("birth_date".to_string(), val.to_string().trim_matches('"').to_string()),
-                                8  => guest_hash.insert("country_of_citizenship".to_string(), val.to_string().trim_matches('"')[0..3].to_string()),
-                                9  => guest_hash.insert("travel_doc_number".to_string(), val.to_string().trim_matches('"').to_string()),
-                                10 => guest_hash.insert("visa_number".to_string(), val.to_string().trim_matches('"').to_string()),
-                                11 => guest_hash.insert("address_abroad".to_string(), val.to_string().trim_matches('"').to_string()),
-                                12 => guest_hash.insert("full_name".to_string(), val.to_string().trim_matches('"').to_string()),
-                                _ => break,
-                            };
-                            i += 1;
-                        }
+                Some(rows) => {
+                    for row in rows {
+                        let row = Self::row_to_strings(row);
+                        let status = row.get(status_column)
+                            .and_then(|value| RegistrationStatus::parse(value))
+                            .unwrap_or(RegistrationStatus::Pending);
+                        for mut guest in columns.guests_from_row(&row_num.to_string(), &row) {
+                            debug!("Found unregistered guest: {}", guest);
+                            guest.status = status;
+
+                            // Check input data format
+                            if guest.has_errors() {
+                                warn!("Unregistered guest {} {} can not be registered: {}", 
+                                    guest.first_name,
+                                    guest.surname,
+                                    guest.get_data_errors())
+                                ;
+                            }
 
-                        // Load hash into Guest Object
-                        let guest = Guest::new(
-                            &row_num,
-                            guest_hash.get("timestamp").unwrap().to_string(),
-                            guest_hash.get("purpose_of_stay").unwrap().to_string(),
-                            guest_hash.get("check_in").unwrap().to_string(),
-                            guest_hash.get("check_out").unwrap().to_string(),
-                            guest_hash.get("surname").unwrap().to_string(),
-                            guest_hash.get("first_name").unwrap().to_string(),
-                            guest_hash.get("birth_date").unwrap().to_string(),
-                            guest_hash.get("country_of_citizenship").unwrap().to_string(),
-                            guest_hash.get("travel_doc_number").unwrap().to_string(),
-                            guest_hash.get("visa_number").unwrap().to_string(),
-                            guest_hash.get("address_abroad").unwrap().to_string(),
-                            guest_hash.get("full_name").unwrap().to_string()
-                        );
-
-                        debug!("Found unregistered guest: {}", guest);
-
-                        unregistered_guests.push(guest.clone());
-
-                        // Check input data format
-                        if !guest.data_errors.is_empty() {
-                            warn!("Unregistered guest {} {} can not be registered: {}", 
-                                guest.first_name,
-                                guest.surname,
-                                guest.get_data_errors())
-                            ;
-                        }                        
+                            unregistered_guests.push(guest);
+                        }
                     }
                 },
                 None => warn!("Empty guest row found"),
             }
-
-            unregistered_guest_count += 1; 
         }
+        // Merge repeated submissions of the same guest
+        duplicates::mark_duplicates(&mut unregistered_guests, &columns.dates);
+
         // Add Guest object to list
-        unregistered_guests
+        Ok(unregistered_guests)
     }
 
-    // Find Google Spreadsheet rows with unregistered guests
-    async fn unregistered_guests(&self) -> Vec<u32> {
-        let form_responses = self.clone().get_unregistered_responses().await;
-        Self::get_unregistered_guests(form_responses)
-    }
+    // Reads stay dates of every guest in Google Spreadsheet with one request for the whole sheet
+    async fn find_stays(&mut self) -> Result<Vec<GuestStay>, CheckinError> {
+        let mut rows = self.get_sheet_response().await?.values.unwrap_or_default().into_iter();
+        let header = match rows.next() {
+            Some(header) => Self::row_to_strings(&header),
+            None => return Ok(Vec::new()),
+        };
+        let columns = Columns::from_header(&header, &self.column_mapping)?;
 
-    // Gets Google Spreadsheet column containing is_registered bool
-    async fn get_unregistered_responses(&self) -> ValueRange{
-        let range = "!M2:M";
-        let sheet_range = format!("{}{}", self.sheet_name, range);
-        let result = self.hub.clone()
-            .unwrap()
-            .spreadsheets()
-            .values_get(&self.spreadsheet_id, &sheet_range)
-            .doit()
-            .await
-            .unwrap();
-
-        result.1
+        Ok((2..).zip(rows)
+            .filter(|(_, row)| !row.is_empty())
+            .map(|(row_num, row): (u32, _)| columns.stay_from_row(&row_num.to_string(), &Self::row_to_strings(&row)))
+            .collect())
     }
 
-    // Checks "Registered With Authorities" column input for unregistered guests
-    fn get_unregistered_guests(response: ValueRange) -> Vec<u32> {
-        let mut guests: Vec<u32> = Vec::new();
-        let mut i: u32 = 2;
-        let mut unregister_guest_count = 0;
-        if let Some(row) = response.values {
-            info!("Checking a total of {} guests", row.iter().count());
-            for cell in row {
-                //let v = cell.get(0)//.unwrap().as_str().unwrap().to_lowercase();
-                
-                match cell.get(0) {
-                    Some(value) => {
-                        let v = value.as_str().unwrap().to_lowercase();
-                        if v.contains("false") {
-                            guests.push(i);
-                            unregister_guest_count += 1;
-                        }
-                    },
-                    None => {
-                        guests.push(i);
-                        unregister_guest_count += 1;
-                    },
-                }
-                i += 1;
-            }
+    // Update rows (Guests) "Registered With Authorities" in spreadsheet with their new status,
+    // and the "Status Updated" and "Run Id" columns, when the sheet has them, with the time
+    // of the change and the run id. When configured, the "Check-in Issues" column is filled
+    // or cleared as well.
+    // All rows are sent in one batch update, which is retried as a unit.
+    async fn mark_guest_states(&self, updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError> {
+        if updates.is_empty() {
+            return Ok(());
         }
 
-        info!("{} unregistered guests found", unregister_guest_count);
+        let Some(status_column) = self.status_column else {
+            return Err(CheckinError::SheetAccess(String::from("Sheet header row has not been read yet")));
+        };
+        let updated_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
 
-        guests
-    }
+        let mut data: Vec<ValueRange> = Vec::new();
+        for update in updates {
+            let mut cells = vec![(status_column, update.status.as_str().to_string())];
+            if let Some(updated_at_column) = self.updated_at_column {
+                cells.push((updated_at_column, updated_at.clone()));
+            }
+            if let Some(run_id_column) = self.run_id_column {
+                cells.push((run_id_column, run_id.to_string()));
+            }
+            if let Some(issues_column) = self.issues_column {
+                cells.push((issues_column, update.issues.clone()));
+            }
 
-    // Gets given rows (guests) from Google Spreadsheet
-    async fn get_guest_rows_response(self, rows: Vec<u32>) -> Vec<ValueRange>{
-        let mut guest_rows = Vec::new();
-        
-        for row in rows {
-            let range = format!("!{}:{}", row, row);
-            let sheet_range = format!("{}{}", self.sheet_name, range);
-            let result = self.hub.clone()
-                .unwrap()
-                .spreadsheets()
-                .values_get(&self.spreadsheet_id, &sheet_range)
+            for (column, value) in cells {
+                data.push(ValueRange {
+                    range: Some(format!("{}!{}{}", self.sheet_name, column_letter(column), update.row)),
+                    values: Some(vec![vec![json!(value)]]),
+                    ..Default::default()
+                });
+            }
+        }
+
+        let req = BatchUpdateValuesRequest {
+            data: Some(data),
+            value_input_option: Some(String::from("RAW")),
+            ..Default::default()
+        };
+
+        let req = &req;
+        let response = self.retry.run("Updating registration status", |mut retry_after| async move {
+            self.hub.spreadsheets()
+                .values_batch_update(req.clone(), &self.spreadsheet_id)
+                .delegate(&mut retry_after)
                 .doit()
                 .await
-                .unwrap();
-            // Log response
-            debug!("log guest_rows_response: {:?}", result.0);
-            guest_rows.push(result.1)
+        }).await?;
+
+        debug!("{:?}", response.1);
+        for update in updates {
+            info!("Updated {} on row {} col 'Registered With Authorities' to {}", update.guest_name, update.row, update.status);
         }
-        guest_rows
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statuses(values: &[&str]) -> Vec<Vec<serde_json::Value>> {
+        values.iter()
+            .map(|value| if value.is_empty() { Vec::new() } else { vec![json!(value)] })
+            .collect()
+    }
+
+    #[test]
+    fn rows_past_the_status_column_are_pending() {
+        // Sheets drops the empty status cells of the last two rows
+        let rows = Reservation::get_unregistered_guests(5, statuses(&["EXPORTED", "PENDING", "SUBMITTED"]), false);
+        assert_eq!(rows, vec![3, 5, 6]);
+    }
+
+    #[test]
+    fn statuses_decide_which_rows_are_exported() {
+        let rows = Reservation::get_unregistered_guests(
+            6,
+            statuses(&["", "INVALID", "EXPORTED", "TRUE", "REJECTED", "done"]),
+            false,
+        );
+        assert_eq!(rows, vec![2, 3]);
+
+        let rows = Reservation::get_unregistered_guests(3, statuses(&["EXPORTED", "TRUE", "FALSE"]), true);
+        assert_eq!(rows, vec![2, 4]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use chrono::{SecondsFormat, Utc};
+use log::{debug, info, warn};
+use serde_derive::{Deserialize, Serialize};
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::columns::Columns;
+use crate::listing::duplicates;
+use crate::listing::guest::Guest;
+use crate::listing::status::{RegistrationStatus, StatusUpdate};
+use crate::settings::ColumnMapping;
+
+
+// Registration state of one form row, as kept next to a local guest file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RowState {
+    pub status: String,
+    pub updated_at: String,
+    pub run_id: String,
+    #[serde(default)]
+    pub issues: String,
+    // Timestamp and document number of the row when its state was set, empty in older files
+    #[serde(default)]
+    pub fingerprint: String,
+}
+
+// JSON sidecar file holding registration state for local guest files, keyed by row number.
+// Each state remembers which submission it belongs to, so sorting or editing the file
+// does not move a state onto another guest.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StateFile {
+    rows: BTreeMap<String, RowState>,
+}
+
+impl StateFile {
+    // Sidecar path for a guest file, e.g. guests.csv -> guests.csv.state.json
+    pub fn path_for(filepath: &str) -> String {
+        format!("{}.state.json", filepath)
+    }
+
+    // Loads the sidecar, a missing file means no row has a state yet
+    pub fn load(path: &str) -> Result<Self, CheckinError> {
+        if !Path::new(path).exists() {
+            return Ok(StateFile::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| CheckinError::Io(path.to_string(), e))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    // Writes the sidecar through a temporary file so a crash never leaves it half written
+    pub fn save(&self, path: &str) -> Result<(), CheckinError> {
+        let tmp_path = format!("{}.tmp", path);
+        let io_error = |e| CheckinError::Io(path.to_string(), e);
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?).map_err(io_error)?;
+        fs::rename(&tmp_path, path).map_err(io_error)?;
+        Ok(())
+    }
+
+    // Raw status text of a row, empty when the row has never been processed. A submission
+    // that moved, e.g. after sorting the file, keeps its state; a row whose state was set
+    // for another submission counts as never processed.
+    pub fn status(&self, row: &str, fingerprint: &str) -> &str {
+        match self.rows.get(row) {
+            Some(state) if state.fingerprint.is_empty() || state.fingerprint == fingerprint => state.status.as_str(),
+            other => match self.rows.iter().find(|(_, state)| state.fingerprint == fingerprint) {
+                Some((old_row, state)) => {
+                    info!("Submission on row {} was on row {} when it was marked {}", row, old_row, state.status);
+                    state.status.as_str()
+                },
+                None => {
+                    if let Some(state) = other {
+                        warn!("Row {} holds another submission than when it was marked {}, treating it as PENDING", row, state.status);
+                    }
+                    ""
+                },
+            },
+        }
+    }
+
+    // Sets the state of a row, dropping the state the submission had on another row
+    pub fn set(&mut self, row: &str, fingerprint: &str, status: RegistrationStatus, run_id: &str, issues: &str) {
+        if !fingerprint.is_empty() {
+            self.rows.retain(|other_row, state| other_row == row || state.fingerprint != fingerprint);
+        }
+        self.rows.insert(row.to_string(), RowState {
+            status: status.as_str().to_string(),
+            updated_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            run_id: run_id.to_string(),
+            issues: issues.to_string(),
+            fingerprint: fingerprint.to_string(),
+        });
+    }
+}
+
+// Identifies the submission on a row by its timestamp and the lead guest's document number
+fn fingerprint(columns: &Columns, row: &[String]) -> String {
+    let cell = |index: usize| row.get(index).map(|value| value.trim()).unwrap_or_default();
+    format!("{}|{}", cell(columns.timestamp), cell(columns.travel_doc_number))
+}
+
+// Finds guests in a local guest file that still need exporting. The first row is the
+// header, data rows are numbered as in a spreadsheet starting from first_row_num.
+// The fingerprint of every row read is kept for mark_guest_states.
+pub fn find_pending_guests(rows: Vec<Vec<String>>, first_row_num: u32, column_mapping: &ColumnMapping,
+                           state_filepath: &str, retry_exported: bool,
+                           fingerprints: &mut HashMap<String, String>) -> Result<Vec<Guest>, CheckinError> {
+    let mut rows = rows.into_iter();
+
+    // Match header row to guest fields
+    let header = match rows.next() {
+        Some(header) => header,
+        None => return Ok(Vec::new()),
+    };
+    let columns = Columns::from_header(&header, column_mapping)?;
+    let state = StateFile::load(state_filepath)?;
+
+    let mut unregistered_guests = Vec::new();
+    for (row_num, row) in (first_row_num + 1..).zip(rows) {
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+
+        let row_num = row_num.to_string();
+        let row_fingerprint = fingerprint(&columns, &row);
+        let row_status = state.status(&row_num, &row_fingerprint);
+        let status = match RegistrationStatus::parse(row_status) {
+            Some(status) if status.needs_export(retry_exported) => status,
+            Some(_) => continue,
+            None => {
+                warn!("Unknown registration status \"{}\" on row {}, skipping", row_status, row_num);
+                continue;
+            },
+        };
+        fingerprints.insert(row_num.clone(), row_fingerprint);
+
+        for mut guest in columns.guests_from_row(&row_num, &row) {
+            debug!("Found unregistered guest: {}", guest);
+            guest.status = status;
+
+            // Check input data format
+            if guest.has_errors() {
+                warn!("Unregistered guest {} {} can not be registered: {}",
+                    guest.first_name,
+                    guest.surname,
+                    guest.get_data_errors()
+                );
+            }
+
+            unregistered_guests.push(guest);
+        }
+    }
+
+    // Merge repeated submissions of the same guest
+    duplicates::mark_duplicates(&mut unregistered_guests, &columns.dates);
+
+    Ok(unregistered_guests)
+}
+
+// Reads the stay dates of every row in a local guest file, the first row being the header
+pub fn find_stays(rows: Vec<Vec<String>>, first_row_num: u32, column_mapping: &ColumnMapping) -> Result<Vec<GuestStay>, CheckinError> {
+    let mut rows = rows.into_iter();
+    let header = match rows.next() {
+        Some(header) => header,
+        None => return Ok(Vec::new()),
+    };
+    let columns = Columns::from_header(&header, column_mapping)?;
+
+    Ok((first_row_num + 1..).zip(rows)
+        .filter(|(_, row)| !row.iter().all(|cell| cell.trim().is_empty()))
+        .map(|(row_num, row)| columns.stay_from_row(&row_num.to_string(), &row))
+        .collect())
+}
+
+// Records the new statuses of a local guest file in its sidecar with a single write,
+// along with the fingerprints of the rows as they were read
+pub fn mark_guest_states(filepath: &str, state_filepath: &str, fingerprints: &HashMap<String, String>,
+                         updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    let mut state = StateFile::load(state_filepath)?;
+    for update in updates {
+        let fingerprint = fingerprints.get(&update.row).map(String::as_str).unwrap_or_default();
+        state.set(&update.row, fingerprint, update.status, run_id, &update.issues);
+    }
+    state.save(state_filepath)?;
+
+    for update in updates {
+        info!("Updated {} on row {} of {} to {}", update.guest_name, update.row, filepath, update.status);
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(guests: &[(&str, &str, &str)]) -> Vec<Vec<String>> {
+        let header = ["Timestamp", "Purpose of stay", "Check-in date", "Check-out date", "Last name", "First name", "Date of birth",
+                      "Citizenship", "Passport number", "Visa number", "Address abroad", "Full name"];
+        std::iter::once(header.map(str::to_string).to_vec())
+            .chain(guests.iter().map(|(timestamp, surname, passport)| {
+                [*timestamp, "10", "01.06.2024", "05.06.2024", surname, "Anna", "14.03.1985", "DEU", passport, "", "Berlin", surname]
+                    .map(str::to_string).to_vec()
+            }))
+            .collect()
+    }
+
+    fn pending(rows: Vec<Vec<String>>, state_filepath: &str, fingerprints: &mut HashMap<String, String>) -> Vec<(String, String)> {
+        find_pending_guests(rows, 1, &ColumnMapping::default(), state_filepath, false, fingerprints).unwrap()
+            .into_iter()
+            .map(|guest| (guest.row, guest.surname))
+            .collect()
+    }
+
+    fn export(state_filepath: &str, fingerprints: &HashMap<String, String>, row: &str) {
+        let update = StatusUpdate {
+            row: row.to_string(),
+            guest_name: String::new(),
+            status: RegistrationStatus::Exported,
+            issues: String::new(),
+        };
+        mark_guest_states("guests.csv", state_filepath, fingerprints, &[update], "run").unwrap();
+    }
+
+    #[test]
+    fn states_follow_their_submission() {
+        let state_filepath = std::env::temp_dir().join(format!("sidecar-test-{}.state.json", std::process::id()));
+        let state_filepath = state_filepath.to_str().unwrap();
+        let mut fingerprints = HashMap::new();
+
+        let file = rows(&[("01.06.2024 10:00:00", "Schmidt", "C01X00T47"), ("02.06.2024 10:00:00", "Meyer", "C01X00T48")]);
+        assert_eq!(pending(file, state_filepath, &mut fingerprints).len(), 2);
+        export(state_filepath, &fingerprints, "2");
+
+        // Sorted by name: Meyer moves to row 2 and is still pending, Schmidt stays exported on row 3
+        let sorted = rows(&[("02.06.2024 10:00:00", "Meyer", "C01X00T48"), ("01.06.2024 10:00:00", "Schmidt", "C01X00T47")]);
+        assert_eq!(pending(sorted, state_filepath, &mut fingerprints), vec![(String::from("2"), String::from("Meyer"))]);
+
+        // Schmidt's row replaced by another submission, which is pending
+        let replaced = rows(&[("03.06.2024 10:00:00", "Novak", "AB1234567"), ("02.06.2024 10:00:00", "Meyer", "C01X00T48")]);
+        assert_eq!(pending(replaced, state_filepath, &mut fingerprints).len(), 2);
+
+        fs::remove_file(state_filepath).unwrap();
+    }
+}
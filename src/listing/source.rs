@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::guest::Guest;
+use crate::listing::status::StatusUpdate;
+
+
+// A backend holding guest form responses and their registration state
+#[async_trait]
+pub trait ReservationSource: Send + Sync {
+    // Finds guests that still need to be registered with the authorities
+    async fn find_pending_guests(&mut self) -> Result<Vec<Guest>, CheckinError>;
+
+    // Reads the stay dates of every form row, whatever its registration status
+    async fn find_stays(&mut self) -> Result<Vec<GuestStay>, CheckinError>;
+
+    // Records the registration state of several guest rows at once. Either every
+    // update is committed or, when an error is returned, none of them are.
+    async fn mark_guest_states(&self, updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError>;
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+
+// Registration lifecycle of a guest row, written to the "Registered With Authorities" column.
+// Runs move rows from PENDING to INVALID or EXPORTED. Ubyport is not read back, so the
+// host sets SUBMITTED once the UNL file is uploaded and ACCEPTED or REJECTED from its
+// answer. A rejected row is picked up again once the host fixes it and sets it to PENDING.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationStatus {
+    Pending,
+    Invalid,
+    Exported,
+    // Set by the host
+    Submitted,
+    Accepted,
+    Rejected,
+    // An older submission of a guest merged into a newer row
+    Superseded,
+}
+
+impl RegistrationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationStatus::Pending => "PENDING",
+            RegistrationStatus::Invalid => "INVALID",
+            RegistrationStatus::Exported => "EXPORTED",
+            RegistrationStatus::Submitted => "SUBMITTED",
+            RegistrationStatus::Accepted => "ACCEPTED",
+            RegistrationStatus::Rejected => "REJECTED",
+            RegistrationStatus::Superseded => "SUPERSEDED",
+        }
+    }
+
+    // Parses a status cell. Empty cells and the legacy FALSE checkbox are pending. The
+    // legacy TRUE checkbox marked rows already registered, so they are never exported again.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_uppercase().as_str() {
+            "" | "FALSE" | "PENDING" => Some(RegistrationStatus::Pending),
+            "INVALID" => Some(RegistrationStatus::Invalid),
+            "EXPORTED" => Some(RegistrationStatus::Exported),
+            "TRUE" | "SUBMITTED" => Some(RegistrationStatus::Submitted),
+            "ACCEPTED" => Some(RegistrationStatus::Accepted),
+            "REJECTED" => Some(RegistrationStatus::Rejected),
+            "SUPERSEDED" => Some(RegistrationStatus::Superseded),
+            _ => None,
+        }
+    }
+
+    // Whether a row in this state should be picked up for export.
+    // Exported rows are only retried when the listing asks for it.
+    pub fn needs_export(&self, retry_exported: bool) -> bool {
+        match self {
+            RegistrationStatus::Pending | RegistrationStatus::Invalid => true,
+            RegistrationStatus::Exported => retry_exported,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for RegistrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+// A pending change to one guest row's registration status
+#[derive(Clone, Debug)]
+pub struct StatusUpdate {
+    pub row: String,
+    pub guest_name: String,
+    pub status: RegistrationStatus,
+    // Check-in issues shown next to the row, empty once the row validates
+    pub issues: String,
+}
+
+impl StatusUpdate {
+    // Comma separated row numbers of the given updates, for log and report messages
+    pub fn rows(updates: &[StatusUpdate]) -> String {
+        updates.iter().map(|u| u.row.as_str()).collect::<Vec<_>>().join(", ")
+    }
+}
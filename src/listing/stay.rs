@@ -0,0 +1,120 @@
+use chrono::{Datelike, NaiveDate};
+use crate::listing::guest::{ErrorCode, Guest, GuestError, GuestField};
+use crate::settings;
+
+
+// Oldest plausible guest, in years at arrival
+const MAX_AGE_YEARS: i32 = 120;
+
+// Per listing rules checking a guest's stay and birth dates against each other.
+// Dates that could not be read are left to input validation.
+#[derive(Debug, Clone)]
+pub struct StayRules {
+    max_stay_nights: i64,
+    max_days_ahead: i64,
+}
+
+impl StayRules {
+    pub fn new(config: &settings::Listing) -> Self {
+        StayRules {
+            max_stay_nights: config.max_stay_nights,
+            max_days_ahead: config.max_arrival_days_ahead,
+        }
+    }
+
+    pub fn check(&self, guest: &Guest, today: NaiveDate) -> Vec<GuestError> {
+        let mut errors = Vec::new();
+        let check_in = guest.check_in.get();
+
+        if let (Some(check_in), Some(check_out)) = (check_in, guest.check_out.get()) {
+            let nights = (check_out - check_in).num_days();
+            if nights <= 0 {
+                errors.push(GuestError::new(GuestField::CheckOut, ErrorCode::Inconsistent, &guest.check_out.to_string())
+                    .with_detail(format!("not after check in date {}", guest.check_in)));
+            } else if nights > self.max_stay_nights {
+                errors.push(GuestError::new(GuestField::CheckOut, ErrorCode::Inconsistent, &guest.check_out.to_string())
+                    .with_detail(format!("stay of {} nights is longer than the {} night maximum", nights, self.max_stay_nights)));
+            }
+        }
+
+        if check_in.is_some_and(|check_in| (check_in - today).num_days() > self.max_days_ahead) {
+            errors.push(GuestError::new(GuestField::CheckIn, ErrorCode::Inconsistent, &guest.check_in.to_string())
+                .with_detail(format!("more than {} days ahead", self.max_days_ahead)));
+        }
+
+        if let Some(birth_date) = guest.birth_date.get() {
+            let error = || GuestError::new(GuestField::BirthDate, ErrorCode::Inconsistent, &guest.birth_date.to_string());
+            if birth_date > today {
+                errors.push(error().with_detail("in the future"));
+            } else if check_in.is_some_and(|check_in| birth_date > check_in) {
+                errors.push(error().with_detail(format!("after check in date {}", guest.check_in)));
+            } else if Self::age(birth_date, check_in.unwrap_or(today)) > MAX_AGE_YEARS {
+                errors.push(error().with_detail(format!("the guest would be older than {}", MAX_AGE_YEARS)));
+            }
+        }
+
+        errors
+    }
+
+    // Full years between birth and the given day
+    fn age(birth_date: NaiveDate, on: NaiveDate) -> i32 {
+        let birthday_passed = (on.month(), on.day()) >= (birth_date.month(), birth_date.day());
+        on.year() - birth_date.year() - i32::from(!birthday_passed)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listing::dates::DateParser;
+
+    const RULES: StayRules = StayRules { max_stay_nights: 30, max_days_ahead: 90 };
+
+    fn guest(check_in: &str, check_out: &str, birth_date: &str) -> Guest {
+        let dates = DateParser::new(None);
+        Guest::new(&String::from("2"), String::new(), Err(String::from("10")), dates.parse(check_in), dates.parse(check_out),
+                   String::from("Schmidt"), String::from("Anna"), dates.parse_birth_date(birth_date), String::from("DEU"),
+                   String::from("C01X00T47"), String::new(), String::new(), String::from("Anna Schmidt"))
+    }
+
+    fn errors(guest: &Guest) -> Vec<(GuestField, Option<String>)> {
+        let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        RULES.check(guest, today).into_iter().map(|error| (error.field, error.detail)).collect()
+    }
+
+    #[test]
+    fn accepts_a_plausible_stay() {
+        assert!(errors(&guest("10.06.2024", "15.06.2024", "14.03.1985")).is_empty());
+        // Babies born this year are fine
+        assert!(errors(&guest("10.06.2024", "15.06.2024", "02.01.2024")).is_empty());
+    }
+
+    #[test]
+    fn checks_stay_length_and_arrival() {
+        assert_eq!(errors(&guest("10.06.2024", "10.06.2024", "14.03.1985")),
+                   [(GuestField::CheckOut, Some(String::from("not after check in date 10.06.2024")))]);
+        assert!(errors(&guest("10.06.2024", "10.07.2024", "14.03.1985")).is_empty());
+        assert_eq!(errors(&guest("10.06.2024", "11.07.2024", "14.03.1985")),
+                   [(GuestField::CheckOut, Some(String::from("stay of 31 nights is longer than the 30 night maximum")))]);
+        assert!(errors(&guest("30.08.2024", "01.09.2024", "14.03.1985")).is_empty());
+        assert_eq!(errors(&guest("31.08.2024", "02.09.2024", "14.03.1985")),
+                   [(GuestField::CheckIn, Some(String::from("more than 90 days ahead")))]);
+    }
+
+    #[test]
+    fn checks_birth_date_against_today_and_the_stay() {
+        assert_eq!(errors(&guest("10.06.2024", "15.06.2024", "02.06.2024")),
+                   [(GuestField::BirthDate, Some(String::from("in the future")))]);
+        assert_eq!(errors(&guest("20.05.2024", "25.05.2024", "22.05.2024")),
+                   [(GuestField::BirthDate, Some(String::from("after check in date 20.05.2024")))]);
+    }
+
+    #[test]
+    fn checks_age_at_arrival() {
+        // 120 at arrival, and 121 when born a day earlier
+        assert!(errors(&guest("10.06.2024", "15.06.2024", "11.06.1903")).is_empty());
+        assert_eq!(errors(&guest("10.06.2024", "15.06.2024", "10.06.1903")),
+                   [(GuestField::BirthDate, Some(String::from("the guest would be older than 120")))]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::unlfile::is_encodable;
+
+
+// Transliterates text to Latin letters Windows-1250 can encode. Characters it can
+// already encode, Czech and most Central European letters, are kept as typed.
+// Names follow the ICAO 9303 tables, so they read as in the passport MRZ:
+// Latin letters with other diacritics, Cyrillic and Arabic. Greek and Hebrew use
+// the common passport spelling, and pinyin loses its tone marks. Chinese
+// characters are not converted, as their reading depends on the word: they and
+// other characters without a table entry are left for the encoding check to report.
+pub fn transliterate(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let lower = |i: usize| chars.get(i).map(|c| c.to_lowercase().next().unwrap_or(*c));
+    let mut latin = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if is_encodable(c) {
+            latin.push(c);
+            continue;
+        }
+        let previous = i.checked_sub(1).and_then(lower);
+        let Some(letters) = greek_in_context(lower(i), previous, lower(i + 1))
+            .or_else(|| lower(i).and_then(latin_letters))
+        else {
+            latin.push(c);
+            continue;
+        };
+
+        let upper_before = i > 0 && chars[i - 1].is_uppercase();
+        let upper_after = chars.get(i + 1).is_some_and(|next| next.is_uppercase());
+        let lower_after = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+        let word_start = i == 0 || !chars[i - 1].is_alphabetic();
+        if c.is_uppercase() && (upper_after || (upper_before && !lower_after)) {
+            // Ж in ЖУК is ZH, in Жанна it is Zh
+            latin.push_str(&letters.to_uppercase());
+        } else if c.is_uppercase() || (!c.is_lowercase() && word_start) {
+            // Scripts without case start each word with a capital
+            let mut letters = letters.chars();
+            latin.extend(letters.next().map(|first| first.to_ascii_uppercase()));
+            latin.push_str(letters.as_str());
+        } else {
+            latin.push_str(letters);
+        }
+    }
+
+    latin
+}
+
+// Greek letters read with their neighbours: γ before γ, ξ or χ is n, ου is u,
+// and αυ, ευ and ηυ are av, ev and iv, or af, ef and if before a voiceless
+// consonant or at the end of a word
+fn greek_in_context(c: Option<char>, previous: Option<char>, next: Option<char>) -> Option<&'static str> {
+    match (c?, previous) {
+        ('γ', _) if matches!(next, Some('γ' | 'ξ' | 'χ')) => Some("n"),
+        ('υ' | 'ύ', Some('ο' | 'ό')) => Some("u"),
+        ('υ' | 'ύ', Some('α' | 'ά' | 'ε' | 'έ' | 'η' | 'ή')) => match next {
+            Some('θ' | 'κ' | 'ξ' | 'π' | 'σ' | 'ς' | 'τ' | 'φ' | 'χ' | 'ψ') => Some("f"),
+            Some(next) if next.is_alphabetic() => Some("v"),
+            _ => Some("f"),
+        },
+        _ => None,
+    }
+}
+
+// Latin spelling of a lower case (or caseless) character
+fn latin_letters(c: char) -> Option<&'static str> {
+    let letters = match c {
+        // Latin letters with diacritics outside Windows-1250 (ICAO 9303)
+        'à' | 'ã' | 'ā' | 'ǎ' => "a",
+        'å' => "aa",
+        'æ' => "ae",
+        'ĉ' | 'ċ' => "c",
+        'ð' | 'đ' => "d",
+        'è' | 'ê' | 'ē' | 'ė' | 'ǝ' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'ï' | 'ī' | 'ǐ' | 'į' | 'ı' | 'ĩ' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ļ' | 'ŀ' => "l",
+        'ñ' | 'ņ' | 'ŋ' => "n",
+        'ò' | 'õ' | 'ō' | 'ǒ' => "o",
+        'ø' | 'œ' => "oe",
+        'ș' => "s",
+        'ț' => "t",
+        'þ' => "th",
+        'ù' | 'û' | 'ū' | 'ǔ' | 'ŭ' | 'ų' | 'ũ' => "u",
+        'ǖ' | 'ǘ' | 'ǚ' | 'ǜ' => "ü",
+        'ŵ' => "w",
+        'ÿ' | 'ŷ' => "y",
+
+        // Cyrillic (ICAO 9303)
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' | 'ѓ' => "g",
+        'д' | 'ђ' => "d",
+        'е' | 'ё' | 'э' => "e",
+        'є' => "ie",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'й' | 'і' | 'ї' => "i",
+        'ј' => "j",
+        'к' | 'ќ' => "k",
+        'л' => "l",
+        'љ' => "lj",
+        'м' => "m",
+        'н' => "n",
+        'њ' => "nj",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'ћ' => "c",
+        'у' | 'ў' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'џ' | 'ѕ' => "dz",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' => "ie",
+        'ы' => "y",
+        'ь' => "",
+        'ю' => "iu",
+        'я' => "ia",
+
+        // Greek
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+
+        // Arabic (ICAO 9303), short vowel marks and tatweel are dropped
+        'ء' => "xe",
+        'آ' => "xaa",
+        'أ' => "xae",
+        'ؤ' => "u",
+        'إ' => "i",
+        'ئ' => "xi",
+        'ا' => "a",
+        'ب' => "b",
+        'ة' => "xta",
+        'ت' => "t",
+        'ث' => "xth",
+        'ج' => "j",
+        'ح' => "xh",
+        'خ' => "xkh",
+        'د' => "d",
+        'ذ' => "xdh",
+        'ر' => "r",
+        'ز' => "z",
+        'س' => "s",
+        'ش' => "xsh",
+        'ص' => "xss",
+        'ض' => "xdz",
+        'ط' => "xtt",
+        'ظ' => "xzz",
+        'ع' => "e",
+        'غ' => "g",
+        'ف' => "f",
+        'ق' => "q",
+        'ك' => "k",
+        'ل' => "l",
+        'م' => "m",
+        'ن' => "n",
+        'ه' => "h",
+        'و' => "w",
+        'ى' => "xay",
+        'ي' => "y",
+        'پ' => "xp",
+        'چ' => "xch",
+        'ژ' => "xzh",
+        'گ' => "xg",
+        'ک' => "k",
+        'ی' => "y",
+        'ـ' | '\u{064B}'..='\u{0652}' => "",
+
+        // Hebrew, consonants only as written
+        'א' | 'ע' => "",
+        'ב' => "b",
+        'ג' => "g",
+        'ד' => "d",
+        'ה' => "h",
+        'ו' => "v",
+        'ז' => "z",
+        'ח' => "kh",
+        'ט' | 'ת' => "t",
+        'י' => "y",
+        'כ' | 'ק' => "k",
+        'ך' => "kh",
+        'ל' => "l",
+        'מ' | 'ם' => "m",
+        'נ' | 'ן' => "n",
+        'ס' => "s",
+        'פ' => "p",
+        'ף' => "f",
+        'צ' | 'ץ' => "ts",
+        'ר' => "r",
+        'ש' => "sh",
+        '\u{05B0}'..='\u{05C7}' => "",
+
+        _ => return None,
+    };
+    Some(letters)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_letters_windows_1250_can_encode() {
+        assert_eq!(transliterate("Šťastný Žluťoučký"), "Šťastný Žluťoučký");
+        assert_eq!(transliterate("Jürgen Łukasz Őry"), "Jürgen Łukasz Őry");
+        assert_eq!(transliterate("O'Neil-Smith"), "O'Neil-Smith");
+    }
+
+    #[test]
+    fn spells_other_latin_letters_as_in_the_mrz() {
+        assert_eq!(transliterate("Núñez"), "Núnez");
+        assert_eq!(transliterate("Øyvind"), "Oeyvind");
+        assert_eq!(transliterate("ÅSA"), "AASA");
+        assert_eq!(transliterate("Hà Đình"), "Ha Đinh");
+    }
+
+    #[test]
+    fn follows_the_case_of_cyrillic_names() {
+        assert_eq!(transliterate("ЖУК"), "ZHUK");
+        assert_eq!(transliterate("Жанна"), "Zhanna");
+        assert_eq!(transliterate("Щербаков Юлия"), "Shcherbakov Iuliia");
+        assert_eq!(transliterate("Ж. ЩУКИН"), "Zh. SHCHUKIN");
+    }
+
+    #[test]
+    fn reads_greek_letters_with_their_neighbours() {
+        assert_eq!(transliterate("Ευάγγελος"), "Evangelos");
+        assert_eq!(transliterate("Ευστάθιος"), "Efstathios");
+        assert_eq!(transliterate("Λουκάς Παύλος"), "Loukas Pavlos");
+        assert_eq!(transliterate("ΓΙΩΡΓΟΣ"), "GIORGOS");
+    }
+
+    #[test]
+    fn capitalises_words_of_scripts_without_case() {
+        assert_eq!(transliterate("محمد"), "Mxhmd");
+        assert_eq!(transliterate("دَاوُد"), "Dawd");
+        assert_eq!(transliterate("דוד שרה"), "Dvd Shrh");
+    }
+
+    #[test]
+    fn leaves_chinese_characters_for_the_encoding_check() {
+        assert_eq!(transliterate("王 Wang"), "王 Wang");
+    }
+}
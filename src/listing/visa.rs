@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use crate::listing::guest::{ErrorCode, Guest, GuestError, GuestField};
+use crate::settings::{self, VisaRequirement, VisaRule};
+
+
+// EU, EEA and Swiss citizens
+const EXEMPT: [&str; 31] = [
+    "AUT", "BEL", "BGR", "HRV", "CYP", "CZE", "DNK", "EST", "FIN", "FRA", "DEU", "GRC", "HUN", "IRL", "ITA",
+    "LVA", "LTU", "LUX", "MLT", "NLD", "POL", "PRT", "ROU", "SVK", "SVN", "ESP", "SWE", "ISL", "LIE", "NOR",
+    "CHE",
+];
+
+// Nationalities without a visa requirement for short stays (Annex II of
+// Regulation (EU) 2018/1806)
+const VISA_FREE: [&str; 64] = [
+    "ALB", "AND", "ATG", "ARG", "AUS", "BHS", "BRB", "BIH", "BRA", "BRN", "CAN", "CHL", "COL", "CRI", "DMA",
+    "SLV", "GEO", "GRD", "GTM", "HND", "HKG", "ISR", "JPN", "KIR", "XKX", "MAC", "MYS", "MHL", "MUS", "MEX",
+    "FSM", "MDA", "MCO", "MNE", "NZL", "NIC", "MKD", "PLW", "PAN", "PRY", "PER", "KNA", "LCA", "VCT", "WSM",
+    "SMR", "SRB", "SYC", "SGP", "SLB", "KOR", "TWN", "TLS", "TON", "TTO", "TUV", "UKR", "ARE", "GBR", "USA",
+    "URY", "VUT", "VAT", "VEN",
+];
+
+// Which nationalities need a visa number, from the built-in lists overridden by [visa]
+#[derive(Debug, Clone)]
+pub struct VisaRules {
+    visa_free_nights: i64,
+    nationalities: HashMap<String, VisaRule>,
+}
+
+impl VisaRules {
+    pub fn new(config: &settings::Visa) -> Self {
+        VisaRules {
+            visa_free_nights: config.visa_free_nights,
+            nationalities: config.nationalities.iter()
+                .map(|(code, rule)| (code.trim().to_uppercase(), rule.clone()))
+                .collect(),
+        }
+    }
+
+    fn rule(&self, code: &str) -> (VisaRequirement, i64) {
+        if let Some(rule) = self.nationalities.get(code) {
+            return (rule.requirement, rule.visa_free_nights.unwrap_or(self.visa_free_nights));
+        }
+        let requirement = if EXEMPT.contains(&code) {
+            VisaRequirement::Exempt
+        } else if VISA_FREE.contains(&code) {
+            VisaRequirement::VisaFree
+        } else {
+            VisaRequirement::Required
+        };
+        (requirement, self.visa_free_nights)
+    }
+
+    // A missing visa number where one is needed, or one given where it makes no
+    // sense. Guests whose citizenship is unknown are left to input validation.
+    pub fn check(&self, guest: &Guest) -> Option<GuestError> {
+        if guest.has_issue(GuestField::CountryOfCitizenship, ErrorCode::UnknownCode) {
+            return None;
+        }
+
+        let code = guest.country_of_citizenship.as_str();
+        let visa_number = guest.get_visa_number();
+        let has_visa = !visa_number.trim().is_empty();
+        let nights = guest.check_in.get()
+            .zip(guest.check_out.get())
+            .map(|(check_in, check_out)| (check_out - check_in).num_days());
+
+        // A visa number given where none is needed is only a warning
+        let missing = || GuestError::new(GuestField::VisaNumber, ErrorCode::Required, "");
+        let not_needed = || GuestError::warning(GuestField::VisaNumber, ErrorCode::NotNeeded, visa_number);
+
+        match self.rule(code) {
+            (VisaRequirement::Exempt, _) if has_visa => Some(not_needed().with_detail(format!(
+                "{} citizens need none", code
+            ))),
+            (VisaRequirement::VisaFree, max_nights) => match nights {
+                Some(nights) if nights > max_nights && !has_visa => Some(missing().with_detail(format!(
+                    "{} citizens need a visa for stays over {} nights, this one is {}", code, max_nights, nights
+                ))),
+                Some(nights) if nights <= max_nights && has_visa => Some(not_needed().with_detail(format!(
+                    "{} citizens need none up to {} nights, this stay is {}", code, max_nights, nights
+                ))),
+                _ => None,
+            },
+            (VisaRequirement::Required, _) if !has_visa => Some(missing().with_detail(format!(
+                "{} citizens need a visa", code
+            ))),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listing::dates::DateParser;
+    use crate::settings::Visa;
+
+    fn guest(citizenship: &str, check_out: &str, visa_number: &str) -> Guest {
+        let dates = DateParser::new(None);
+        Guest::new(&String::from("2"), String::new(), Err(String::from("10")), dates.parse("01.06.2024"), dates.parse(check_out),
+                   String::from("Doe"), String::from("Alex"), dates.parse_birth_date("14.03.1985"), citizenship.to_string(),
+                   String::from("C01X00T47"), visa_number.to_string(), String::new(), String::from("Alex Doe"))
+    }
+
+    fn rules(nationalities: &[(&str, VisaRequirement, Option<i64>)]) -> VisaRules {
+        VisaRules::new(&Visa {
+            visa_free_nights: 90,
+            nationalities: nationalities.iter()
+                .map(|&(code, requirement, visa_free_nights)| (code.to_string(), VisaRule { requirement, visa_free_nights }))
+                .collect(),
+        })
+    }
+
+    // Code and severity of the visa issue, if any
+    fn issue(rules: &VisaRules, guest: &Guest) -> Option<(ErrorCode, bool)> {
+        rules.check(guest).map(|error| (error.code, error.is_warning()))
+    }
+
+    #[test]
+    fn exempt_citizens_never_need_a_visa() {
+        let rules = rules(&[]);
+        assert_eq!(issue(&rules, &guest("Germany", "01.12.2024", "")), None);
+        assert_eq!(issue(&rules, &guest("SVK", "05.06.2024", "CZE123456")), Some((ErrorCode::NotNeeded, true)));
+    }
+
+    #[test]
+    fn visa_free_citizens_need_one_for_long_stays() {
+        let rules = rules(&[]);
+        assert_eq!(issue(&rules, &guest("USA", "30.08.2024", "")), None);
+        assert_eq!(issue(&rules, &guest("USA", "31.08.2024", "")), Some((ErrorCode::Required, false)));
+        assert_eq!(issue(&rules, &guest("USA", "31.08.2024", "CZE123456")), None);
+        assert_eq!(issue(&rules, &guest("USA", "05.06.2024", "CZE123456")), Some((ErrorCode::NotNeeded, true)));
+    }
+
+    #[test]
+    fn other_citizens_need_a_visa() {
+        let rules = rules(&[]);
+        assert_eq!(issue(&rules, &guest("IND", "05.06.2024", "")), Some((ErrorCode::Required, false)));
+        assert_eq!(issue(&rules, &guest("IND", "05.06.2024", "CZE123456")), None);
+        // Unknown citizenship is reported by input validation instead
+        assert_eq!(issue(&rules, &guest("Atlantis", "05.06.2024", "")), None);
+    }
+
+    #[test]
+    fn configured_nationalities_override_the_built_in_lists() {
+        let rules = rules(&[
+            ("ind", VisaRequirement::VisaFree, None),
+            ("USA", VisaRequirement::VisaFree, Some(14)),
+            ("GBR", VisaRequirement::Required, None),
+        ]);
+        assert_eq!(issue(&rules, &guest("IND", "05.06.2024", "")), None);
+        assert_eq!(issue(&rules, &guest("USA", "15.06.2024", "")), None);
+        assert_eq!(issue(&rules, &guest("USA", "16.06.2024", "")), Some((ErrorCode::Required, false)));
+        assert_eq!(issue(&rules, &guest("GBR", "05.06.2024", "")), Some((ErrorCode::Required, false)));
+    }
+}
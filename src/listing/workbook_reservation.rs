@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use async_trait::async_trait;
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use log::info;
+use crate::error::CheckinError;
+use crate::listing::calendar::GuestStay;
+use crate::listing::guest::Guest;
+use crate::listing::sidecar::{self, StateFile};
+use crate::listing::source::ReservationSource;
+use crate::listing::status::StatusUpdate;
+use crate::settings::ColumnMapping;
+
+
+// Guest details kept in an .xlsx, .xls or .ods workbook, with registration state kept in a sidecar file
+pub struct WorkbookReservation {
+    filepath: String,
+    sheet_name: Option<String>,
+    state_filepath: String,
+    column_mapping: ColumnMapping,
+    retry_exported: bool,
+    // Fingerprints of the rows last read, stored with their new states
+    fingerprints: HashMap<String, String>,
+}
+
+impl WorkbookReservation {
+    // Reads sheet_name from the workbook, or its first sheet when none is given
+    pub fn new(filepath: &str, sheet_name: Option<&str>, column_mapping: &ColumnMapping, retry_exported: bool) -> Self {
+        WorkbookReservation {
+            filepath: filepath.to_string(),
+            sheet_name: sheet_name.map(str::to_string),
+            state_filepath: StateFile::path_for(filepath),
+            column_mapping: column_mapping.clone(),
+            retry_exported,
+            fingerprints: HashMap::new(),
+        }
+    }
+
+    // Reads all used rows of the sheet along with the sheet name and number of the first row
+    fn read_rows(&self) -> Result<(String, Vec<Vec<String>>, u32), CheckinError> {
+        let mut workbook = open_workbook_auto(&self.filepath)?;
+        let sheet_name = match &self.sheet_name {
+            Some(name) => name.clone(),
+            None => workbook.sheet_names().first().cloned().ok_or_else(|| CheckinError::SheetAccess(format!("No sheet found in workbook {}", self.filepath)))?,
+        };
+        let range = workbook.worksheet_range(&sheet_name)?;
+
+        let rows: Vec<Vec<String>> = range.rows()
+            .map(|row| row.iter().map(Self::cell_to_string).collect())
+            .collect();
+
+        // The range starts at the first used cell, keep row numbers as shown in the workbook
+        let first_row_num = range.start().map(|(row, _)| row + 1).unwrap_or(1);
+
+        Ok((sheet_name, rows, first_row_num))
+    }
+
+    // Renders a cell the way Google Sheets formats it, dates as dd.mm.yyyy
+    fn cell_to_string(cell: &Data) -> String {
+        match cell {
+            Data::Float(f) if f.fract() == 0.0 => format!("{}", *f as i64),
+            Data::DateTime(_) => match cell.as_datetime() {
+                Some(dt) if dt.time() == chrono::NaiveTime::MIN => dt.format("%d.%m.%Y").to_string(),
+                Some(dt) => dt.format("%d.%m.%Y %H:%M:%S").to_string(),
+                None => cell.to_string(),
+            },
+            _ => cell.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ReservationSource for WorkbookReservation {
+    // Finds guests in the workbook that have not been registered in Ubyport
+    async fn find_pending_guests(&mut self) -> Result<Vec<Guest>, CheckinError> {
+        let (sheet_name, rows, first_row_num) = self.read_rows()?;
+        let unregistered_guests = sidecar::find_pending_guests(
+            rows,
+            first_row_num,
+            &self.column_mapping,
+            &self.state_filepath,
+            self.retry_exported,
+            &mut self.fingerprints,
+        )?;
+
+        info!("{} unregistered guests found in {} sheet {}", unregistered_guests.len(), self.filepath, sheet_name);
+
+        Ok(unregistered_guests)
+    }
+
+    async fn find_stays(&mut self) -> Result<Vec<GuestStay>, CheckinError> {
+        let (_, rows, first_row_num) = self.read_rows()?;
+        sidecar::find_stays(rows, first_row_num, &self.column_mapping)
+    }
+
+    // Records the new statuses in the sidecar state file with a single write
+    async fn mark_guest_states(&self, updates: &[StatusUpdate], run_id: &str) -> Result<(), CheckinError> {
+        sidecar::mark_guest_states(&self.filepath, &self.state_filepath, &self.fingerprints, updates, run_id)
+    }
+}
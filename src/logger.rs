@@ -1,11 +1,10 @@
 use std::time::SystemTime;
-use humantime;
 use log::LevelFilter;
 
 pub struct Logger {}
 
 impl Logger {
-    pub fn new(log_level: LevelFilter, file_path: &str) -> Result<(), fern::InitError> {
+    pub fn init(log_level: LevelFilter, file_path: &str) -> Result<(), fern::InitError> {
         fern::Dispatch::new()
             .format(|out, message, record| {
                 out.finish(format_args!(
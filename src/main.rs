@@ -3,22 +3,29 @@ mod settings;
 mod logger;
 mod unlfile;
 mod email;
+mod error;
+mod retry;
 
-use listing::Listing;
+use listing::{build_hub, ArrivalDecision, Listing, RegistrationStatus, SheetsHub, StatusUpdate, VisaRules};
 use unlfile::UnlFile;
-use email::Email;
+use email::{Email, EmailTable};
+use retry::RetryPolicy;
 use logger::Logger;
-use log::{info, error};
-use settings::Settings;
-use tokio;
+use log::{info, warn, error};
+use chrono::Utc;
+use settings::{Settings, SheetsApi};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinSet};
 
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>>{
     let settings = Settings::new()?;
-    Logger::new(log::LevelFilter::Info, "./output.log")?;
+    Logger::init(log::LevelFilter::Info, "./output.log")?;
 
     info!(r"                       _          _               _    _       ");
     info!(r"                      | |        | |             | |  (_)      ");
@@ -29,7 +36,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
     info!(r"  __/ |                                                        ");
     info!(r" |___/                                                         ");
 
-    info!("Starting Guest Checkin...");
+    // Identifies this run in the sheet next to every status change
+    let run_id = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    info!("Starting Guest Checkin run {}...", run_id);
 
     /////////////////////////////////////////////////////////////////
     // Create UNL file for each listing which contains:            //
@@ -40,6 +49,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
     let mut unl_files: Vec<UnlFile> = Vec::new();
     let mut all_unreg_guests: Vec<Vec<String>> = Vec::new();
     let mut all_checkin_issues: Vec<Vec<String>> = Vec::new();
+    let mut all_uncommitted_updates: Vec<Vec<String>> = Vec::new();
+    let mut all_bookings_without_guests: Vec<Vec<String>> = Vec::new();
+    let mut all_stays_without_booking: Vec<Vec<String>> = Vec::new();
+    let mut all_booking_date_differences: Vec<Vec<String>> = Vec::new();
+    let mut all_excluded_guests: Vec<Vec<String>> = Vec::new();
+    let mut all_late_guests: Vec<Vec<String>> = Vec::new();
+    let mut all_merged_duplicates: Vec<Vec<String>> = Vec::new();
+    let mut all_guest_warnings: Vec<Vec<String>> = Vec::new();
+    let mut all_transliterated: Vec<Vec<String>> = Vec::new();
+    let mut all_listing_errors: Vec<Vec<String>> = Vec::new();
     
     // Create UNL file directory 
     let path = Path::new(&settings.unl_file_directory);
@@ -52,88 +71,80 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
         info!("Directory {} already exists.", path.display());
     }
 
-    for listing in settings.listing {
-        let listing: Listing = Listing::new(
-            &listing.id,
-            &listing.name,
-            &listing.address,
-            &listing.google_spreadsheet_id,
-            &listing.google_sheet_name,
-            &listing.a_record,
-            &settings.service_account_key_filepath,
-
-        ).await;
-
-        info!("Listing: {}", listing.get_name());
-        
-        // Find Unregistered Guests
-        let mut unreg_guests = listing.find_unregistered_guests().await;
-
-        // Remove unregistered guests with checkin issues
-        for (_index, guest) in unreg_guests.iter().enumerate() {
-            if !guest.data_errors.is_empty() {
-                all_checkin_issues.push(
-                    vec![listing.get_name().to_string(),
-                    guest.row.clone(),
-                    format!("{} {}", guest.first_name, guest.surname ),
-                    guest.get_data_errors()]
-                );
-            }
+    // One authenticated Sheets client shared by every listing without its own service account
+    let shared_hub = if settings.uses_shared_hub() {
+        match build_hub(&settings.sheets, &settings.service_account_key_filepath).await {
+            Ok(hub) => Some(hub),
+            Err(e) => {
+                error!("Shared Google Sheets client not created: {}", e);
+                all_listing_errors.push(vec![String::from("All Google Sheets listings"), e.to_string()]);
+                None
+            },
         }
+    } else {
+        None
+    };
 
-        unreg_guests.retain(|guest| guest.data_errors.is_empty());
+    // Retries of failed Sheets calls, budgeted across the whole run
+    let retry_policy = Arc::new(RetryPolicy::new(&settings.retry));
 
-        if !unreg_guests.is_empty() {
+    // Visa requirements by nationality, the same for every listing
+    let visa_rules = Arc::new(VisaRules::new(&settings.visa));
 
-            // Get filepath
-            let file_name = format!("{}{}{}", settings.unl_file_directory, listing.get_name(), ".unl");
+    // Process listings concurrently, at most max_concurrent_listings at a time
+    let worker_limit = Arc::new(Semaphore::new(settings.max_concurrent_listings.max(1)));
+    let mut workers = JoinSet::new();
+    let mut worker_listings: HashMap<task::Id, String> = HashMap::new();
+    for (index, listing) in settings.listing.into_iter().enumerate() {
+        let worker_limit = worker_limit.clone();
+        let sheets_api = settings.sheets.clone();
+        let shared_hub = shared_hub.clone();
+        let unl_file_directory = settings.unl_file_directory.clone();
+        let run_id = run_id.clone();
+        let retry_policy = retry_policy.clone();
+        let visa_rules = visa_rules.clone();
+        let name = listing.name.clone();
+        let worker = workers.spawn(async move {
+            let _permit = worker_limit.acquire_owned().await.expect("Listing worker pool closed");
+            (index, process_listing(listing, sheets_api, shared_hub, retry_policy, visa_rules, unl_file_directory, run_id).await)
+        });
+        worker_listings.insert(worker.id(), name);
+    }
 
-            // Find u_records
-            let mut u_records: Vec<String> = Vec::new();
-            for guest in &unreg_guests {
-                let u_record = guest.get_u_record();
-                u_records.push(u_record);
-            }
+    let mut reports: Vec<(usize, ListingReport)> = Vec::new();
+    while let Some(result) = workers.join_next().await {
+        match result {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                let name = worker_listings.get(&e.id()).map(String::as_str).unwrap_or("?");
+                error!("Listing {} failed: {}", name, e);
+                all_listing_errors.push(vec![name.to_string(), e.to_string()]);
+            },
+        }
+    }
 
-            // Create UNL file
-            let result= UnlFile::new(&listing.get_a_record(), u_records, &file_name);
-            match result {
-                Ok(unl_file) => {
-                    info!("UNLFile created successfully");
-                    unl_files.push(unl_file);
-                    
-                    
-                    // Prepare unregistered guests for email
-                    for guest in &unreg_guests {
-                        info!("{}", guest);
-                        
-                        all_unreg_guests.push(
-                            vec![listing.get_name().to_string(),
-                            guest.row.clone(),
-                            format!("{} {}", guest.first_name, guest.surname ),
-                            guest.check_in.clone(),
-                            guest.check_out.clone()]
-                        );
-
-                        // Update guest as registered
-                        listing.update_guest_as_registered(
-                            &guest.row,
-                            &guest.first_name,
-                            &guest.surname
-                        ).await;
-                    }
-                }
-                Err(e) => {
-                    error!("Error: {}", e);
-                    // Send admin email error
-                    // #### Add Here ####
-                    continue;
-                },
-            }
-        } else {
-            info!("No unregistered guests found for {}", listing.get_name());
+    // Combine results in config order, as a sequential run would
+    reports.sort_by_key(|(index, _)| *index);
+    for (_, report) in reports {
+        unl_files.extend(report.unl_file);
+        all_unreg_guests.extend(report.unreg_guests);
+        all_checkin_issues.extend(report.checkin_issues);
+        all_uncommitted_updates.extend(report.uncommitted_updates);
+        all_bookings_without_guests.extend(report.bookings_without_guests);
+        all_stays_without_booking.extend(report.stays_without_booking);
+        all_booking_date_differences.extend(report.booking_date_differences);
+        all_excluded_guests.extend(report.excluded_guests);
+        all_late_guests.extend(report.late_guests);
+        all_merged_duplicates.extend(report.merged_duplicates);
+        all_guest_warnings.extend(report.guest_warnings);
+        all_transliterated.extend(report.transliterated);
+        all_listing_errors.extend(report.errors);
+    }
 
-        }
+    let retry_stats = retry_policy.stats();
+    if retry_stats.retries > 0 || retry_stats.failed > 0 {
+        info!("Sheets calls retried {} time(s), {} call(s) recovered, {} failed",
+            retry_stats.retries, retry_stats.recovered, retry_stats.failed);
     }
 
     ///////////
@@ -147,9 +158,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
         attachments.push(file.get_filename().to_string());
     }
 
-    // Add Errors
-    // todo
-
     let mail = Email::new(
         attachments, 
         settings.ses.from,
@@ -159,9 +167,116 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
         &settings.aws.secret_key,
         &settings.aws.region,
     );
+    let mail = match mail {
+        Ok(mail) => mail,
+        Err(e) => {
+            error!("Report email not sent: {}", e);
+            return Err(e.into());
+        },
+    };
+
+    let mut tables = vec![
+        EmailTable::new(
+            "Guests Available for Checkin",
+            &["Listing", "Row", "Fullname", "Check In", "Check Out"],
+            all_unreg_guests,
+        ),
+        EmailTable::new(
+            "Guests with Checkin Issues",
+            &["Listing", "Row", "Fullname", "Input Error(s)"],
+            all_checkin_issues,
+        ),
+    ];
+
+    if !all_guest_warnings.is_empty() {
+        tables.push(EmailTable::new(
+            "Guest Data Warnings",
+            &["Listing", "Row", "Fullname", "Warning(s)"],
+            all_guest_warnings,
+        ));
+    }
+    if !all_transliterated.is_empty() {
+        tables.push(EmailTable::new(
+            "Transliterated Guest Names",
+            &["Listing", "Row", "Field", "Original", "Transliterated"],
+            all_transliterated,
+        ));
+    }
+    if !all_merged_duplicates.is_empty() {
+        tables.push(EmailTable::new(
+            "Merged Duplicates",
+            &["Listing", "Row", "Fullname", "Check In", "Kept Row"],
+            all_merged_duplicates,
+        ));
+    }
+    if !all_late_guests.is_empty() {
+        tables.push(EmailTable::new(
+            "Guests Past Reporting Deadline",
+            &["Listing", "Row", "Fullname", "Check In", "Reason"],
+            all_late_guests,
+        ));
+    }
+    if !all_excluded_guests.is_empty() {
+        tables.push(EmailTable::new(
+            "Guests Not Exported This Run",
+            &["Listing", "Row", "Fullname", "Check In", "Reason"],
+            all_excluded_guests,
+        ));
+    }
+
+    // Add Errors
+    if !all_listing_errors.is_empty() {
+        tables.push(EmailTable::new(
+            "Listing Errors",
+            &["Listing", "Error"],
+            all_listing_errors,
+        ));
+    }
+    if !all_bookings_without_guests.is_empty() {
+        tables.push(EmailTable::new(
+            "Bookings Without Guest Registration",
+            &["Listing", "Booking", "Check In", "Check Out"],
+            all_bookings_without_guests,
+        ));
+    }
+    if !all_stays_without_booking.is_empty() {
+        tables.push(EmailTable::new(
+            "Guest Rows Matching No Booking",
+            &["Listing", "Row", "Fullname", "Check In", "Check Out"],
+            all_stays_without_booking,
+        ));
+    }
+    if !all_booking_date_differences.is_empty() {
+        tables.push(EmailTable::new(
+            "Guest Rows With Other Dates Than Their Booking",
+            &["Listing", "Booking", "Booked", "Row", "Fullname", "On Form"],
+            all_booking_date_differences,
+        ));
+    }
+    if retry_stats.retries > 0 || retry_stats.failed > 0 {
+        tables.push(EmailTable::new(
+            "Google Sheets Retries",
+            &["Retries", "Recovered Calls", "Failed Calls", "Retry Budget"],
+            vec![vec![retry_stats.retries.to_string(),
+                retry_stats.recovered.to_string(),
+                retry_stats.failed.to_string(),
+                format!("{} of {} used{}", retry_stats.retries, retry_stats.run_budget,
+                    if retry_stats.budget_exhausted { ", exhausted" } else { "" })]],
+        ));
+    }
+    if !all_uncommitted_updates.is_empty() {
+        tables.push(EmailTable::new(
+            "Sheet Status Updates Not Committed",
+            &["Listing", "Row", "Fullname", "Status", "Error"],
+            all_uncommitted_updates,
+        ));
+    }
 
     // Send Mail
-        mail.send(all_unreg_guests, all_checkin_issues).await;
+    if let Err(e) = mail.send(&tables).await {
+        error!("Report email not sent: {}", e);
+        return Err(e.into());
+    }
 
     // Upon unsuccessful email delivery, mark all guests as unregistered
     // for listing in settings.listing {
@@ -170,4 +285,295 @@ async fn main() -> Result<(), Box<dyn std::error::Error>>{
 
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Rows one listing contributes to the run, kept apart until all listings finish
+#[derive(Default)]
+struct ListingReport {
+    unl_file: Option<UnlFile>,
+    unreg_guests: Vec<Vec<String>>,
+    checkin_issues: Vec<Vec<String>>,
+    uncommitted_updates: Vec<Vec<String>>,
+    bookings_without_guests: Vec<Vec<String>>,
+    stays_without_booking: Vec<Vec<String>>,
+    booking_date_differences: Vec<Vec<String>>,
+    excluded_guests: Vec<Vec<String>>,
+    merged_duplicates: Vec<Vec<String>>,
+    guest_warnings: Vec<Vec<String>>,
+    transliterated: Vec<Vec<String>>,
+    late_guests: Vec<Vec<String>>,
+    errors: Vec<Vec<String>>,
+}
+
+async fn process_listing(
+    config: settings::Listing,
+    sheets_api: SheetsApi,
+    shared_hub: Option<SheetsHub>,
+    retry_policy: Arc<RetryPolicy>,
+    visa_rules: Arc<VisaRules>,
+    unl_file_directory: String,
+    run_id: String,
+) -> ListingReport {
+    let mut report = ListingReport::default();
+    let mut listing: Listing = match Listing::new(&config, &sheets_api, shared_hub.as_ref(), &retry_policy, &visa_rules).await {
+        Ok(listing) => listing,
+        Err(e) => {
+            error!("Skipping listing {}: {}", config.name, e);
+            report.errors.push(vec![config.name.clone(), e.to_string()]);
+            return report;
+        },
+    };
+
+    info!("Listing: {}", listing.get_name());
+    
+    // Find Unregistered Guests
+    let mut unreg_guests = match listing.find_unregistered_guests().await {
+        Ok(guests) => guests,
+        Err(e) => {
+            error!("Skipping listing {}: {}", listing.get_name(), e);
+            report.errors.push(vec![listing.get_name().to_string(), e.to_string()]);
+            return report;
+        },
+    };
+
+    // Cross-check bookings calendar against guest form rows
+    match listing.check_calendar().await {
+        Ok(Some(calendar)) => {
+            for booking in calendar.bookings_without_guests {
+                warn!("Booking {} from {} has no guest registration", booking.summary, booking.check_in);
+                report.bookings_without_guests.push(
+                    vec![listing.get_name().to_string(),
+                    booking.summary,
+                    booking.check_in.format("%d.%m.%Y").to_string(),
+                    booking.check_out.format("%d.%m.%Y").to_string()]
+                );
+            }
+            for (booking, stay) in calendar.date_differences {
+                warn!("Guest {} on row {} has other dates than booking {}", stay.guest_name, stay.row, booking.summary);
+                report.booking_date_differences.push(
+                    vec![listing.get_name().to_string(),
+                    booking.summary,
+                    format!("{} - {}", booking.check_in.format("%d.%m.%Y"), booking.check_out.format("%d.%m.%Y")),
+                    stay.row,
+                    stay.guest_name,
+                    format!("{} - {}", stay.check_in, stay.check_out)]
+                );
+            }
+            for stay in calendar.stays_without_booking {
+                warn!("Guest {} on row {} matches no booking", stay.guest_name, stay.row);
+                report.stays_without_booking.push(
+                    vec![listing.get_name().to_string(),
+                    stay.row,
+                    stay.guest_name,
+                    stay.check_in.to_string(),
+                    stay.check_out.to_string()]
+                );
+            }
+        },
+        Ok(None) => (),
+        Err(e) => {
+            error!("Checking calendar for {}: {}", listing.get_name(), e);
+            report.errors.push(vec![listing.get_name().to_string(), e.to_string()]);
+        },
+    }
+
+    // Status changes for this listing, committed together at the end
+    let mut status_updates: Vec<StatusUpdate> = Vec::new();
+
+    // Drop older submissions merged into a newer row of the same guest
+    for guest in unreg_guests.iter() {
+        if let Some(newer_row) = &guest.superseded_by {
+            report.merged_duplicates.push(
+                vec![listing.get_name().to_string(),
+                guest.get_row_label(),
+                format!("{} {}", guest.first_name, guest.surname),
+                guest.check_in.to_string(),
+                newer_row.clone()]
+            );
+
+            // One status per row, its companions follow the lead guest
+            if guest.guest_number > 1 {
+                continue;
+            }
+            status_updates.push(StatusUpdate {
+                row: guest.row.clone(),
+                guest_name: format!("{} {}", guest.first_name, guest.surname),
+                status: RegistrationStatus::Superseded,
+                issues: format!("Superseded by row {}", newer_row),
+            });
+        }
+    }
+
+    unreg_guests.retain(|guest| guest.superseded_by.is_none());
+
+    // Remove rows with checkin issues, all guests of a row are registered together
+    let mut invalid_rows: Vec<String> = Vec::new();
+    for guest in unreg_guests.iter() {
+        if guest.has_errors() {
+            report.checkin_issues.push(
+                vec![listing.get_name().to_string(),
+                guest.get_row_label(),
+                format!("{} {}", guest.first_name, guest.surname ),
+                guest.get_data_errors()]
+            );
+
+            let issues = match guest.guest_number {
+                1 => guest.get_data_errors(),
+                n => format!("Guest {}: {}", n, guest.get_data_errors()),
+            };
+            match status_updates.iter_mut().find(|update| update.row == guest.row) {
+                Some(update) => update.issues = format!("{}; {}", update.issues, issues),
+                None => {
+                    // The row is named after its lead guest, even when only a companion failed
+                    let lead = unreg_guests.iter()
+                        .find(|lead| lead.row == guest.row && lead.guest_number == 1)
+                        .unwrap_or(guest);
+                    invalid_rows.push(guest.row.clone());
+                    status_updates.push(StatusUpdate {
+                        row: guest.row.clone(),
+                        guest_name: format!("{} {}", lead.first_name, lead.surname),
+                        status: RegistrationStatus::Invalid,
+                        issues,
+                    });
+                },
+            }
+        }
+    }
+
+    unreg_guests.retain(|guest| !invalid_rows.contains(&guest.row));
+
+    // Warnings do not hold a guest back, they are listed for a look
+    for guest in unreg_guests.iter().filter(|guest| guest.has_warnings()) {
+        report.guest_warnings.push(
+            vec![listing.get_name().to_string(),
+            guest.get_row_label(),
+            format!("{} {}", guest.first_name, guest.surname),
+            guest.get_data_warnings()]
+        );
+    }
+
+    // Names written in Latin letters for the UNL file, for the host to compare
+    for guest in unreg_guests.iter() {
+        for (field, original, latin) in &guest.transliterated {
+            report.transliterated.push(
+                vec![listing.get_name().to_string(),
+                guest.get_row_label(),
+                field.to_string(),
+                original.clone(),
+                latin.clone()]
+            );
+        }
+    }
+
+    // Hold back guests outside the listing's arrival rules, they stay pending
+    unreg_guests.retain(|guest| match listing.check_arrival(guest) {
+        ArrivalDecision::Export => true,
+        ArrivalDecision::ExportLate(reason) => {
+            warn!("Guest {} {} on row {} is late: {}", guest.first_name, guest.surname, guest.row, reason);
+            report.late_guests.push(
+                vec![listing.get_name().to_string(),
+                guest.get_row_label(),
+                format!("{} {}", guest.first_name, guest.surname),
+                guest.check_in.to_string(),
+                reason]
+            );
+            true
+        },
+        ArrivalDecision::Exclude(reason) => {
+            info!("Not exporting guest {} {} on row {}: {}", guest.first_name, guest.surname, guest.row, reason);
+            report.excluded_guests.push(
+                vec![listing.get_name().to_string(),
+                guest.get_row_label(),
+                format!("{} {}", guest.first_name, guest.surname),
+                guest.check_in.to_string(),
+                reason]
+            );
+
+            // A row fixed since it was marked invalid is pending again, without its old issues
+            if guest.guest_number == 1 && guest.status == RegistrationStatus::Invalid {
+                status_updates.push(StatusUpdate {
+                    row: guest.row.clone(),
+                    guest_name: format!("{} {}", guest.first_name, guest.surname),
+                    status: RegistrationStatus::Pending,
+                    issues: String::new(),
+                });
+            }
+            false
+        },
+    });
+
+    if !unreg_guests.is_empty() {
+
+        // Get filepath
+        let file_name = format!("{}{}{}", unl_file_directory, listing.get_name(), ".unl");
+
+        // Find u_records
+        let mut u_records: Vec<String> = Vec::new();
+        for guest in &unreg_guests {
+            let u_record = guest.get_u_record();
+            u_records.push(u_record);
+        }
+
+        // Create UNL file
+        let result= UnlFile::new(listing.get_a_record(), u_records, &file_name);
+        match result {
+            Ok(unl_file) => {
+                info!("UNLFile created successfully");
+                report.unl_file = Some(unl_file);
+                
+                
+                // Prepare unregistered guests for email
+                for guest in &unreg_guests {
+                    info!("{}", guest);
+                    
+                    report.unreg_guests.push(
+                        vec![listing.get_name().to_string(),
+                        guest.get_row_label(),
+                        format!("{} {}", guest.first_name, guest.surname ),
+                        guest.check_in.to_string(),
+                        guest.check_out.to_string()]
+                    );
+
+                    // Update the row as exported once, with its lead guest
+                    if guest.guest_number > 1 {
+                        continue;
+                    }
+                    status_updates.push(StatusUpdate {
+                        row: guest.row.clone(),
+                        guest_name: format!("{} {}", guest.first_name, guest.surname),
+                        status: RegistrationStatus::Exported,
+                        issues: String::new(),
+                    });
+                }
+            }
+            Err(e) => {
+                error!("Error: {}", e);
+                report.errors.push(vec![listing.get_name().to_string(), e.to_string()]);
+            },
+        }
+    } else {
+        info!("No unregistered guests found for {}", listing.get_name());
+
+    }
+
+    // Commit status changes for the listing as one unit
+    if !status_updates.is_empty() {
+        match listing.update_guest_statuses(&status_updates, &run_id).await {
+            Ok(_) => info!("Committed status of rows {} for {}", StatusUpdate::rows(&status_updates), listing.get_name()),
+            Err(e) => {
+                error!("Status of rows {} for {} NOT committed: {}", StatusUpdate::rows(&status_updates), listing.get_name(), e);
+                for update in &status_updates {
+                    report.uncommitted_updates.push(
+                        vec![listing.get_name().to_string(),
+                        update.row.clone(),
+                        update.guest_name.clone(),
+                        update.status.to_string(),
+                        e.to_string()]
+                    );
+                }
+            },
+        }
+    }
+
+    report
+}
@@ -0,0 +1,187 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use google_sheets4::client::{Delegate, Retry};
+use google_sheets4::hyper::{self, header::RETRY_AFTER, StatusCode};
+use google_sheets4::Error;
+use log::warn;
+use crate::settings;
+
+
+// Retry counts of a run, reported in the summary email
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RetryStats {
+    pub retries: u32,
+    pub recovered: u32,
+    pub failed: u32,
+    pub run_budget: u32,
+    pub budget_exhausted: bool,
+}
+
+// Retry policy for Google Sheets calls, shared by all listings of a run.
+// Transient failures (HTTP 429/5xx, timeouts, connection and token refresh
+// errors) are retried with exponential backoff and jitter, or after the delay
+// the server asks for in Retry-After, until the call's attempts or the run's
+// retry budget run out.
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    request_timeout: Duration,
+    run_budget: u32,
+    retries: AtomicU32,
+    recovered: AtomicU32,
+    failed: AtomicU32,
+    budget_exhausted: AtomicBool,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &settings::Retry) -> Self {
+        RetryPolicy {
+            max_attempts: config.max_attempts.max(1),
+            base_delay: Duration::from_millis(config.base_delay_ms),
+            max_delay: Duration::from_secs(config.max_delay_secs),
+            request_timeout: Duration::from_secs(config.request_timeout_secs),
+            run_budget: config.run_budget,
+            retries: AtomicU32::new(0),
+            recovered: AtomicU32::new(0),
+            failed: AtomicU32::new(0),
+            budget_exhausted: AtomicBool::new(false),
+        }
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        RetryStats {
+            retries: self.retries.load(Ordering::SeqCst),
+            recovered: self.recovered.load(Ordering::SeqCst),
+            failed: self.failed.load(Ordering::SeqCst),
+            run_budget: self.run_budget,
+            budget_exhausted: self.budget_exhausted.load(Ordering::SeqCst),
+        }
+    }
+
+    // Runs a Sheets call, retrying transient failures. The call is handed a
+    // delegate to attach to the request so a Retry-After header is picked up.
+    pub async fn run<T, F, Fut>(&self, what: &str, mut call: F) -> Result<T, Error>
+    where
+        F: FnMut(RetryAfter) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let retry_after = RetryAfter::default();
+            let result = match tokio::time::timeout(self.request_timeout, call(retry_after.clone())).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("No response within {}s", self.request_timeout.as_secs()),
+                ))),
+            };
+
+            let error = match result {
+                Ok(value) => {
+                    if attempt > 1 {
+                        self.recovered.fetch_add(1, Ordering::SeqCst);
+                    }
+                    return Ok(value);
+                },
+                Err(e) if !Self::is_transient(&e) => return Err(e),
+                Err(e) => e,
+            };
+
+            let delay = retry_after.get().unwrap_or_else(|| self.backoff(attempt));
+            if attempt >= self.max_attempts {
+                warn!("{} failed after {} attempts: {}", what, attempt, error.to_string().trim_end());
+            } else if delay > self.max_delay {
+                warn!("{} failed and the server asked to retry after {}s, more than max_delay_secs: {}",
+                    what, delay.as_secs(), error.to_string().trim_end());
+            } else if !self.take_retry() {
+                warn!("{} failed and the run's retry budget of {} is used up: {}",
+                    what, self.run_budget, error.to_string().trim_end());
+            } else {
+                warn!("{} failed (attempt {} of {}), retrying in {:.1}s: {}",
+                    what, attempt, self.max_attempts, delay.as_secs_f32(), error.to_string().trim_end());
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            self.failed.fetch_add(1, Ordering::SeqCst);
+            return Err(error);
+        }
+    }
+
+    // Whether a failed call may succeed when sent again
+    fn is_transient(error: &Error) -> bool {
+        match error {
+            Error::HttpError(_) | Error::MissingToken(_) | Error::Io(_) => true,
+            Error::BadRequest(body) => body["error"]["code"].as_u64()
+                .and_then(|code| StatusCode::from_u16(code as u16).ok())
+                .is_some_and(Self::is_transient_status),
+            Error::Failure(response) => Self::is_transient_status(response.status()),
+            _ => false,
+        }
+    }
+
+    fn is_transient_status(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status.is_server_error()
+    }
+
+    // Exponential backoff with jitter between half and the full delay, so
+    // listings hitting the same quota do not retry in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay / 2 + delay.mul_f64(jitter / 2.0)
+    }
+
+    // Takes one retry from the run's budget
+    fn take_retry(&self) -> bool {
+        let taken = self.retries
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| (used < self.run_budget).then_some(used + 1))
+            .is_ok();
+        if !taken {
+            self.budget_exhausted.store(true, Ordering::SeqCst);
+        }
+        taken
+    }
+}
+
+// Request delegate remembering the Retry-After header of a failed response
+#[derive(Clone, Default)]
+pub struct RetryAfter(Arc<Mutex<Option<Duration>>>);
+
+impl RetryAfter {
+    fn get(&self) -> Option<Duration> {
+        *self.0.lock().unwrap()
+    }
+
+    // Retry-After is either a number of seconds or an HTTP date
+    fn parse(value: &str) -> Option<Duration> {
+        let value = value.trim();
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        let at = DateTime::parse_from_rfc2822(value).ok()?;
+        Some((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+    }
+}
+
+impl Delegate for RetryAfter {
+    fn http_failure(&mut self, response: &hyper::Response<hyper::body::Body>, _err: Option<serde_json::Value>) -> Retry {
+        *self.0.lock().unwrap() = response.headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse);
+        Retry::Abort
+    }
+}
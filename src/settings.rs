@@ -1,23 +1,377 @@
 use config::{Config, File, ConfigError};
 use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::env;
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[allow(unused)]
 pub struct Listing {
     pub id: String,
     pub name: String,
     pub address: String,
+    #[serde(default)]
     pub google_client_id: String,
+    #[serde(default)]
     pub google_client_secret: String,
+    #[serde(default)]
+    pub source: SourceKind,
+    #[serde(default)]
+    pub source_filepath: String,
+    #[serde(default)]
+    pub workbook_sheet_name: Option<String>,
+    #[serde(default)]
     pub google_spreadsheet_id: String,
+    #[serde(default)]
+    pub service_account_key_filepath: Option<String>,
+    #[serde(default)]
     pub google_sheet_name: String,
-    pub a_record: String
+    pub a_record: String,
+    #[serde(default)]
+    pub columns: ColumnMapping,
+    #[serde(default)]
+    pub retry_exported: bool,
+    #[serde(default)]
+    pub ical: Option<String>,
+    #[serde(default = "Listing::default_ical_lookback_days")]
+    pub ical_lookback_days: i64,
+    #[serde(default)]
+    pub arrival_window_days: Option<i64>,
+    #[serde(default)]
+    pub hold_future_arrivals: bool,
+    #[serde(default)]
+    pub reporting_deadline_days: Option<i64>,
+    #[serde(default = "Listing::default_max_stay_nights")]
+    pub max_stay_nights: i64,
+    #[serde(default = "Listing::default_max_arrival_days_ahead")]
+    pub max_arrival_days_ahead: i64,
+}
+
+impl Listing {
+    fn default_ical_lookback_days() -> i64 {
+        14
+    }
+
+    fn default_max_stay_nights() -> i64 {
+        90
+    }
+
+    fn default_max_arrival_days_ahead() -> i64 {
+        365
+    }
+
+    // The listing's own service account key, if it does not use the shared one
+    pub fn own_service_account_key(&self) -> Option<&str> {
+        self.service_account_key_filepath.as_deref().filter(|path| !path.is_empty())
+    }
+}
+
+// Where a listing's guest form responses are read from
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    #[default]
+    GoogleSheets,
+    Csv,
+    Workbook,
+}
+
+// Sheet header names for each guest field, matched case-insensitively. Each defaults
+// to the question of the original guest form, so configs without a mapping still work.
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct ColumnMapping {
+    #[serde(default = "ColumnMapping::default_timestamp")]
+    pub timestamp: String,
+    #[serde(default = "ColumnMapping::default_purpose_of_stay")]
+    pub purpose_of_stay: String,
+    #[serde(default = "ColumnMapping::default_check_in")]
+    pub check_in: String,
+    #[serde(default = "ColumnMapping::default_check_out")]
+    pub check_out: String,
+    #[serde(default = "ColumnMapping::default_surname")]
+    pub surname: String,
+    #[serde(default = "ColumnMapping::default_first_name")]
+    pub first_name: String,
+    #[serde(default = "ColumnMapping::default_birth_date")]
+    pub birth_date: String,
+    #[serde(default = "ColumnMapping::default_country_of_citizenship")]
+    pub country_of_citizenship: String,
+    #[serde(default = "ColumnMapping::default_travel_doc_number")]
+    pub travel_doc_number: String,
+    #[serde(default = "ColumnMapping::default_visa_number")]
+    pub visa_number: String,
+    #[serde(default = "ColumnMapping::default_address_abroad")]
+    pub address_abroad: String,
+    #[serde(default = "ColumnMapping::default_full_name")]
+    pub full_name: String,
+    #[serde(default = "ColumnMapping::default_registered")]
+    pub registered: String,
+    // Optional columns for the time of the last status change and the run that made it, Google Sheets only
+    #[serde(default = "ColumnMapping::default_status_updated_at")]
+    pub status_updated_at: String,
+    #[serde(default = "ColumnMapping::default_run_id")]
+    pub run_id: String,
+    #[serde(default)]
+    pub checkin_issues: Option<String>,
+    #[serde(default)]
+    pub companions: Option<CompanionMapping>,
+    // Optional column with the machine readable zone of the guest's travel document
+    #[serde(default)]
+    pub mrz: Option<String>,
+    // Extra purpose of stay answers of the form, mapped to their Ubyport code
+    #[serde(default)]
+    pub purpose_labels: HashMap<String, String>,
+    // Locale of the form, e.g. "en-US", deciding the day and month order of dates like 1/6/2024
+    #[serde(default)]
+    pub form_locale: Option<String>,
+}
+
+impl ColumnMapping {
+    fn default_timestamp() -> String {
+        String::from("Timestamp")
+    }
+
+    fn default_purpose_of_stay() -> String {
+        String::from("Purpose of stay")
+    }
+
+    fn default_check_in() -> String {
+        String::from("Check-in date")
+    }
+
+    fn default_check_out() -> String {
+        String::from("Check-out date")
+    }
+
+    fn default_surname() -> String {
+        String::from("Last name")
+    }
+
+    fn default_first_name() -> String {
+        String::from("First name")
+    }
+
+    fn default_birth_date() -> String {
+        String::from("Date of birth")
+    }
+
+    fn default_country_of_citizenship() -> String {
+        String::from("Citizenship")
+    }
+
+    fn default_travel_doc_number() -> String {
+        String::from("Passport number")
+    }
+
+    fn default_visa_number() -> String {
+        String::from("Visa number")
+    }
+
+    fn default_address_abroad() -> String {
+        String::from("Address abroad")
+    }
+
+    fn default_full_name() -> String {
+        String::from("Full name")
+    }
+
+    fn default_registered() -> String {
+        String::from("Registered With Authorities")
+    }
+
+    fn default_status_updated_at() -> String {
+        String::from("Status Updated")
+    }
+
+    fn default_run_id() -> String {
+        String::from("Run Id")
+    }
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        ColumnMapping {
+            timestamp: Self::default_timestamp(),
+            purpose_of_stay: Self::default_purpose_of_stay(),
+            check_in: Self::default_check_in(),
+            check_out: Self::default_check_out(),
+            surname: Self::default_surname(),
+            first_name: Self::default_first_name(),
+            birth_date: Self::default_birth_date(),
+            country_of_citizenship: Self::default_country_of_citizenship(),
+            travel_doc_number: Self::default_travel_doc_number(),
+            visa_number: Self::default_visa_number(),
+            address_abroad: Self::default_address_abroad(),
+            full_name: Self::default_full_name(),
+            registered: Self::default_registered(),
+            status_updated_at: Self::default_status_updated_at(),
+            run_id: Self::default_run_id(),
+            checkin_issues: None,
+            companions: None,
+            mrz: None,
+            purpose_labels: HashMap::new(),
+            form_locale: None,
+        }
+    }
+}
+
+// Repeated question groups for the lead guest's companions. Headers contain "{n}",
+// replaced by the guest number from 2 up to max_guests.
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct CompanionMapping {
+    #[serde(default = "CompanionMapping::default_max_guests")]
+    pub max_guests: u32,
+    pub surname: String,
+    pub first_name: String,
+    pub birth_date: String,
+    pub country_of_citizenship: String,
+    pub travel_doc_number: String,
+    #[serde(default)]
+    pub visa_number: Option<String>,
+    #[serde(default)]
+    pub address_abroad: Option<String>,
+    #[serde(default)]
+    pub mrz: Option<String>,
+}
+
+impl CompanionMapping {
+    fn default_max_guests() -> u32 {
+        10
+    }
+}
+
+// Google Sheets API endpoint, overridable to run against a local fake server
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct SheetsApi {
+    #[serde(default = "SheetsApi::default_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub auth: SheetsAuth,
+}
+
+impl SheetsApi {
+    fn default_base_url() -> String {
+        String::from("https://sheets.googleapis.com/")
+    }
+}
+
+impl Default for SheetsApi {
+    fn default() -> Self {
+        SheetsApi {
+            base_url: Self::default_base_url(),
+            auth: SheetsAuth::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SheetsAuth {
+    #[default]
+    ServiceAccount,
+    None,
+}
+
+// Retries of transient Google Sheets failures, shared by all listings in a run
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct Retry {
+    #[serde(default = "Retry::default_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "Retry::default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    #[serde(default = "Retry::default_max_delay_secs")]
+    pub max_delay_secs: u64,
+    #[serde(default = "Retry::default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    #[serde(default = "Retry::default_run_budget")]
+    pub run_budget: u32,
+}
+
+impl Retry {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_base_delay_ms() -> u64 {
+        500
+    }
+
+    fn default_max_delay_secs() -> u64 {
+        60
+    }
+
+    fn default_request_timeout_secs() -> u64 {
+        30
+    }
+
+    fn default_run_budget() -> u32 {
+        50
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_attempts: Self::default_max_attempts(),
+            base_delay_ms: Self::default_base_delay_ms(),
+            max_delay_secs: Self::default_max_delay_secs(),
+            request_timeout_secs: Self::default_request_timeout_secs(),
+            run_budget: Self::default_run_budget(),
+        }
+    }
+}
+
+// Visa requirement of a nationality for short Schengen stays
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VisaRequirement {
+    // EU, EEA and Swiss citizens, who never need a visa
+    Exempt,
+    // No visa up to a number of nights
+    VisaFree,
+    Required,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct VisaRule {
+    pub requirement: VisaRequirement,
+    #[serde(default)]
+    pub visa_free_nights: Option<i64>,
+}
+
+// Overrides of the built-in visa rules, keyed on the Ubyport nationality code
+#[derive(Clone, Debug, Deserialize)]
+#[allow(unused)]
+pub struct Visa {
+    #[serde(default = "Visa::default_visa_free_nights")]
+    pub visa_free_nights: i64,
+    #[serde(default)]
+    pub nationalities: HashMap<String, VisaRule>,
+}
+
+impl Visa {
+    fn default_visa_free_nights() -> i64 {
+        90
+    }
+}
+
+impl Default for Visa {
+    fn default() -> Self {
+        Visa {
+            visa_free_nights: Self::default_visa_free_nights(),
+            nationalities: HashMap::new(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
-pub struct AWS {
+pub struct Aws {
     pub region: String,
     pub stage: String,
     pub api_id: String,
@@ -27,7 +381,7 @@ pub struct AWS {
 
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
-pub struct SES {
+pub struct Ses {
     pub from: String,
     pub to: Vec<String>,
 }
@@ -35,18 +389,40 @@ pub struct SES {
 #[derive(Debug, Deserialize)]
 #[allow(unused)]
 pub struct Settings {
-    pub aws: AWS,
-    pub ses: SES,
+    pub aws: Aws,
+    pub ses: Ses,
+    #[serde(default)]
+    pub sheets: SheetsApi,
+    #[serde(default)]
+    pub retry: Retry,
+    #[serde(default)]
+    pub visa: Visa,
     pub listing: Vec<Listing>,
     pub log_filepath: String,
     pub unl_file_directory: String,
     pub service_account_key_filepath: String,
+    #[serde(default = "Settings::default_max_concurrent_listings")]
+    pub max_concurrent_listings: usize,
 }
 
 impl Settings {
-    pub fn new() -> Result<Self, ConfigError> {        
+    fn default_max_concurrent_listings() -> usize {
+        4
+    }
+
+    // Whether any Google Sheets listing relies on the shared service account
+    pub fn uses_shared_hub(&self) -> bool {
+        self.listing.iter()
+            .any(|listing| listing.source == SourceKind::GoogleSheets && listing.own_service_account_key().is_none())
+    }
+
+    pub fn new() -> Result<Self, ConfigError> {
+        // GUEST_CHECKIN_CONFIG points a run at another config, e.g. one using the fake Sheets server
+        let config_path = env::var("GUEST_CHECKIN_CONFIG")
+            .unwrap_or_else(|_| String::from("src/config/config.toml"));
+
         let s = Config::builder()
-            .add_source(File::with_name("src/config/config.toml"))
+            .add_source(File::with_name(&config_path))
             .build()?;
 
         s.try_deserialize()
@@ -1,53 +1,36 @@
 use encoding_rs::WINDOWS_1250;
 use std::fs::File;
-use std::io::{Write};
-use std::error::Error;
-use std::fmt;
+use std::io::{self, Write};
+use crate::error::CheckinError;
 
 
-#[derive(Debug)]
-pub enum UnlFileError {
-    IOError(std::io::Error),
-}
-
-impl From<std::io::Error> for UnlFileError {
-    fn from(error: std::io::Error) -> Self {
-        UnlFileError::IOError(error)
-    }
-}
-
-impl fmt::Display for UnlFileError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            UnlFileError::IOError(field) => write!(f, "Standard IO Error for {}", field),
-        }
-    }
-}
-
-impl Error for UnlFileError {}
-
 pub struct UnlFile {
     file_name: String,
-    unl_file: File,
 }
 
 impl UnlFile {
-    pub fn new(a_record: &str, u_records: Vec<String>, file_name: &str) -> Result<Self, UnlFileError> {
+    pub fn new(a_record: &str, u_records: Vec<String>, file_name: &str) -> Result<Self, CheckinError> {
+        let unl_error = |e| CheckinError::UnlWrite(file_name.to_string(), e);
+
+        // Encode every record before the previous file is replaced
+        let mut records = vec![Self::encode_record(a_record, "A-record").map_err(unl_error)?];
+        for (i, u_record) in u_records.iter().enumerate() {
+            records.push(Self::encode_record(u_record, &format!("U-record {}", i + 1)).map_err(unl_error)?);
+        }
+
         // Create File
-        let mut file: File = File::create(file_name)?;
+        let mut file: File = File::create(file_name).map_err(unl_error)?;
 
-        // Add A-Record
-        Self::add_a_record(&mut file, a_record)?;
-        
-        // Add U-Record
-        Self::add_u_records(&mut file, u_records)?;
+        // Add A-Record and U-Records
+        for record in records {
+            file.write_all(&record).map_err(unl_error)?;
+        }
 
         // Ensure file is flushed
-        file.flush()?;
+        file.flush().map_err(unl_error)?;
 
         Ok(
             Self {
-            unl_file: file,
             file_name: file_name.to_string(),
             }
         )
@@ -55,21 +38,39 @@ impl UnlFile {
 
     pub fn get_filename(&self) -> &str { &self.file_name }
 
-    fn add_a_record(file: &mut File, a_record: &str) -> Result<(), UnlFileError> {
-        let record = format!("{}\r\n", a_record);
-        let (encoded, _, _) = WINDOWS_1250.encode(&record);
-        file.write_all(&encoded)?;
+    // Guest input is checked for unmappable characters beforehand, a record that
+    // still has some is refused rather than written with HTML entities. The error
+    // names only the record, as records hold passport numbers and birth dates.
+    fn encode_record(record: &str, name: &str) -> std::io::Result<Vec<u8>> {
+        let (encoded, _, had_unmappable) = WINDOWS_1250.encode(record);
+        if had_unmappable {
+            let chars: String = unmappable_characters(record).into_iter().collect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has characters Windows-1250 can not encode: {}", name, chars),
+            ));
+        }
+        let mut line = encoded.into_owned();
+        line.extend_from_slice(b"\r\n");
 
-        Ok(())
+        Ok(line)
     }
+}
 
-    fn add_u_records(file: &mut File, u_records: Vec<String>) -> Result<(), UnlFileError> {
-        for u_record in u_records {
-            let record = format!("{}\r\n", u_record);
-            let (encoded, _, _) = WINDOWS_1250.encode(&record);
-            file.write_all(&encoded)?;
-        }
+// Whether the UNL file's Windows-1250 encoding has a code for a character
+pub fn is_encodable(c: char) -> bool {
+    let mut buffer = [0; 4];
+    let (_, _, had_unmappable) = WINDOWS_1250.encode(c.encode_utf8(&mut buffer));
+    !had_unmappable
+}
 
-        Ok(())
+// Characters of a value Windows-1250 can not encode, each listed once
+pub fn unmappable_characters(value: &str) -> Vec<char> {
+    let mut chars: Vec<char> = Vec::new();
+    for c in value.chars() {
+        if !is_encodable(c) && !chars.contains(&c) {
+            chars.push(c);
+        }
     }
+    chars
 }
\ No newline at end of file
@@ -0,0 +1,177 @@
+// Runs guest-checkin against the fake Sheets server and checks what the run wrote back
+// to the sheet of src/config/fake_sheets.json.
+
+use std::fs;
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::Value;
+
+
+const SPREADSHEET_ID: &str = "fixture-listing-1";
+
+struct FakeSheets {
+    process: Child,
+    address: String,
+}
+
+impl FakeSheets {
+    // Starts the server on a free port, failing the first `failures` batch updates with 503
+    fn start(failures: u32) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let address = format!("127.0.0.1:{}", port);
+        let process = Command::new(env!("CARGO_BIN_EXE_fake_sheets"))
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/config/fake_sheets.json"))
+            .arg(&address)
+            .env("FAKE_SHEETS_FAIL_FIRST", failures.to_string())
+            .env("FAKE_SHEETS_FAIL_ONLY", "values:batchUpdate")
+            .env("FAKE_SHEETS_RETRY_AFTER", "0")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        while TcpStream::connect(&address).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "fake Sheets server did not start");
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        FakeSheets { process, address }
+    }
+
+    // The whole sheet as rows of strings
+    fn sheet(&self) -> Vec<Vec<String>> {
+        let url = format!("http://{}/v4/spreadsheets/{}/values/Form%20Responses%201", self.address, SPREADSHEET_ID);
+        let body = tokio::runtime::Runtime::new().unwrap()
+            .block_on(async { reqwest::get(&url).await?.text().await })
+            .unwrap();
+        let body: Value = serde_json::from_str(&body).unwrap();
+
+        body["values"].as_array().unwrap().iter()
+            .map(|row| row.as_array().unwrap().iter().map(|cell| cell.as_str().unwrap_or_default().to_string()).collect())
+            .collect()
+    }
+
+    // Stops the server and returns its log
+    fn stop(mut self) -> String {
+        self.process.kill().unwrap();
+        self.process.wait().unwrap();
+        let mut log = String::new();
+        self.process.stdout.take().unwrap().read_to_string(&mut log).unwrap();
+        log
+    }
+}
+
+// A run directory with a config pointing the listing at the fake server
+fn run_dir(name: &str, address: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("guest-checkin-{}-{}", name, std::process::id()));
+    fs::create_dir_all(dir.join("UNL")).unwrap();
+    fs::write(dir.join("config.toml"), format!(r#"
+log_filepath = "./output.log"
+unl_file_directory = "{unl}/"
+service_account_key_filepath = "./none.json"
+
+[[listing]]
+id = "1"
+name = "fakelisting"
+address = ""
+google_spreadsheet_id = "{SPREADSHEET_ID}"
+google_sheet_name = "Form Responses 1"
+a_record = "A|1|AddDate"
+
+[listing.columns]
+checkin_issues = "Check-in Issues"
+
+[sheets]
+base_url = "http://{address}/"
+auth = "none"
+
+[retry]
+base_delay_ms = 10
+
+[AWS]
+region = "eu-central-1"
+stage = ""
+api_id = ""
+access_key = ""
+secret_key = ""
+
+[SES]
+from = ""
+to = [""]
+"#, unl = dir.join("UNL").display())).unwrap();
+    dir
+}
+
+fn run_guest_checkin(dir: &Path) {
+    // The report email can not be sent from tests, so the exit status is not checked
+    Command::new(env!("CARGO_BIN_EXE_guest-checkin"))
+        .current_dir(dir)
+        .env("GUEST_CHECKIN_CONFIG", dir.join("config.toml"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+}
+
+#[test]
+fn run_writes_statuses_in_one_batch_after_retrying_503() {
+    let server = FakeSheets::start(2);
+    let dir = run_dir("batch", &server.address);
+    run_guest_checkin(&dir);
+    let sheet = server.sheet();
+    let log = server.stop();
+
+    // Two failed attempts and the one that went through
+    assert_eq!(log.matches("Failing request with 503").count(), 2);
+    assert_eq!(log.matches("values:batchUpdate").count(), 3);
+    for range in ["M3 -> [[\"EXPORTED\"]]", "M4 -> [[\"INVALID\"]]", "M5 -> [[\"EXPORTED\"]]", "P3 -> [[\"\"]]"] {
+        assert!(log.contains(&format!("Updated {} Form Responses 1!{}", SPREADSHEET_ID, range)), "{} not written", range);
+    }
+    assert!(!log.contains("Form Responses 1!M2 ->"));
+
+    let header = &sheet[0];
+    let column = |name: &str| header.iter().position(|h| h == name).unwrap();
+    let cell = |row: usize, name: &str| sheet[row - 1].get(column(name)).cloned().unwrap_or_default();
+
+    // Row 2 was exported by an earlier run and is left alone
+    assert_eq!(cell(2, "Registered With Authorities"), "EXPORTED");
+    assert_eq!(cell(2, "Run Id"), "20240601T200000Z");
+
+    // Marco Rossi and John Smith are exported, Giulia Rossi's passport number is too short
+    assert_eq!(cell(3, "Registered With Authorities"), "EXPORTED");
+    assert_eq!(cell(5, "Registered With Authorities"), "EXPORTED");
+    assert_eq!(cell(4, "Registered With Authorities"), "INVALID");
+    assert!(!cell(4, "Check-in Issues").is_empty());
+    assert_eq!(cell(3, "Check-in Issues"), "");
+
+    // Every row of the batch carries the same run id and time
+    let run_id = cell(3, "Run Id");
+    assert!(!run_id.is_empty() && run_id != "20240601T200000Z");
+    assert!([4, 5].iter().all(|&row| cell(row, "Run Id") == run_id));
+    assert!([4, 5].iter().all(|&row| cell(row, "Status Updated") == cell(3, "Status Updated")));
+
+    let unl = fs::read(dir.join("UNL/fakelisting.unl")).unwrap();
+    let unl = String::from_utf8_lossy(&unl);
+    assert_eq!(unl.lines().filter(|line| line.starts_with("U|")).count(), 2);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn run_writes_nothing_when_the_batch_update_keeps_failing() {
+    let server = FakeSheets::start(100);
+    let dir = run_dir("failing", &server.address);
+    run_guest_checkin(&dir);
+    let sheet = server.sheet();
+    server.stop();
+
+    let status = sheet[0].iter().position(|h| h == "Registered With Authorities").unwrap();
+    let statuses: Vec<&str> = sheet[1..].iter().map(|row| row.get(status).map(String::as_str).unwrap_or_default()).collect();
+    assert_eq!(statuses, ["EXPORTED", "FALSE", "", "PENDING"]);
+
+    fs::remove_dir_all(dir).unwrap();
+}